@@ -0,0 +1,198 @@
+use crate::collector::CollectorError;
+use crate::database::*;
+use crate::jira_client::*;
+use crate::models::*;
+use itertools::Itertools;
+
+enum HistoryChange<'a> {
+    Estimate(i64),
+    Remaining(i64),
+    Spent(i64),
+    Status(&'a str),
+}
+
+/// Rebuilds the snapshots of an issue from before it was first collected, using the original and
+/// remaining estimate and status changes from its changelog and the time logged in its worklogs.
+/// These only cover the issue itself, unlike the collected aggregate time tracking, which also
+/// includes sub-tasks.
+fn reconstruct_history(
+    first_collected: &IssueLog,
+    issue: &IssueWithChangelog,
+    worklogs: &[Worklog],
+) -> Vec<IssueLog> {
+    let Some(created) = parse_jira_datetime(&issue.fields.created) else {
+        return Vec::new();
+    };
+    let Ok(first_collected_at) = chrono::DateTime::parse_from_rfc3339(&first_collected.updated_at) else {
+        return Vec::new();
+    };
+
+    let field_changes = |field: &'static str| {
+        issue
+            .changelog
+            .histories
+            .iter()
+            .flat_map(move |history| {
+                history
+                    .items
+                    .iter()
+                    .filter(move |item| item.field == field)
+                    .map(move |item| (history, item))
+            })
+            .filter_map(|(history, item)| Some((parse_jira_datetime(&history.created)?, item)))
+            .sorted_by_key(|(date, _)| *date)
+            .collect::<Vec<_>>()
+    };
+    let estimate_changes = field_changes("timeoriginalestimate");
+    let remaining_changes = field_changes("timeestimate");
+    let status_changes = field_changes("status");
+
+    let parse_seconds = |value: &Option<String>| {
+        value
+            .as_deref()
+            .and_then(|value| value.parse::<i64>().ok())
+            .unwrap_or(0)
+    };
+    let mut time_estimate = estimate_changes
+        .first()
+        .map(|(_, item)| parse_seconds(&item.from))
+        .unwrap_or(first_collected.time_estimate);
+    let mut remaining_estimate = remaining_changes
+        .first()
+        .map(|(_, item)| parse_seconds(&item.from))
+        .unwrap_or(first_collected.remaining_estimate);
+    let mut time_spent = 0;
+    let mut status = status_changes
+        .first()
+        .and_then(|(_, item)| item.from_text.as_deref())
+        .unwrap_or(&first_collected.status);
+
+    let changes = estimate_changes
+        .iter()
+        .map(|(date, item)| (*date, HistoryChange::Estimate(parse_seconds(&item.to))))
+        .chain(
+            remaining_changes
+                .iter()
+                .map(|(date, item)| (*date, HistoryChange::Remaining(parse_seconds(&item.to)))),
+        )
+        .chain(status_changes.iter().filter_map(|(date, item)| {
+            Some((*date, HistoryChange::Status(item.to_text.as_deref()?)))
+        }))
+        .chain(worklogs.iter().filter_map(|worklog| {
+            Some((
+                parse_jira_datetime(&worklog.started)?,
+                HistoryChange::Spent(worklog.time_spent_seconds as i64),
+            ))
+        }))
+        .sorted_by_key(|(date, _)| *date)
+        .collect::<Vec<_>>();
+
+    // The story points, sprints and resolution at the time are unknown, so they are left empty
+    // rather than copied from a later snapshot. The changelog only names statuses, so their
+    // category is only known for the status the issue was first collected in.
+    let snapshot = |date: chrono::DateTime<chrono::Utc>, time_estimate: i64, remaining_estimate: i64, time_spent: i64, status: &str| {
+        IssueLog {
+            key: first_collected.key.clone(),
+            summary: first_collected.summary.clone(),
+            epic_key: first_collected.epic_key.clone(),
+            time_estimate,
+            time_spent,
+            remaining_estimate,
+            story_points: None,
+            updated_at: date.to_rfc3339(),
+            assignee: first_collected.assignee.clone(),
+            status: status.to_string(),
+            status_category: if status == first_collected.status {
+                first_collected.status_category.clone()
+            } else {
+                String::new()
+            },
+            issue_type: first_collected.issue_type.clone(),
+            priority: first_collected.priority.clone(),
+            resolution: String::new(),
+            resolution_date: None,
+            labels: first_collected.labels.clone(),
+            sprint_ids: Vec::new(),
+        }
+    };
+
+    let mut history = vec![snapshot(created, time_estimate, remaining_estimate, time_spent, status)];
+    for (date, change) in changes {
+        if date >= first_collected_at || date < created {
+            continue;
+        }
+        match change {
+            HistoryChange::Estimate(estimate) => time_estimate = estimate,
+            HistoryChange::Remaining(remaining) => remaining_estimate = remaining,
+            HistoryChange::Spent(spent) => time_spent += spent,
+            HistoryChange::Status(to) => status = to,
+        }
+
+        let previous = history.last().unwrap();
+        if previous.time_estimate == time_estimate
+            && previous.remaining_estimate == remaining_estimate
+            && previous.time_spent == time_spent
+            && previous.status == status
+        {
+            continue;
+        }
+        history.push(snapshot(date, time_estimate, remaining_estimate, time_spent, status));
+    }
+
+    history
+        .into_iter()
+        .filter(|log| log.updated_at < first_collected.updated_at)
+        .collect()
+}
+
+/// Backfills the history of every stored issue of the given epic, or of all stored epics.
+/// Snapshots that already exist are skipped, so this can safely be run repeatedly. Issues with
+/// sub-tasks are skipped, their reconstructed history would not add up to the collected totals.
+pub async fn backfill(
+    jira_client: &JiraClient,
+    database: &Database,
+    epic_key: Option<&str>,
+) -> Result<(), CollectorError> {
+    let epic_keys = match epic_key {
+        Some(epic_key) => vec![epic_key.to_string()],
+        None => database
+            .get_epics()
+            .await?
+            .into_iter()
+            .map(|epic| epic.key)
+            .collect(),
+    };
+
+    let issue_logs = database.get_all_latest_issue_logs().await?;
+
+    for epic_key in epic_keys {
+        println!("Backfilling epic {}...", epic_key);
+        let mut inserted = 0;
+
+        for issue_log in issue_logs.iter().filter(|log| log.epic_key == epic_key) {
+            let first_collected = database
+                .get_logs_of_issue(&issue_log.key)
+                .await?
+                .into_iter()
+                .min_by(|a, b| a.updated_at.cmp(&b.updated_at))
+                .expect("Stored issue has no snapshots");
+            let issue = jira_client.get_issue_with_changelog(&issue_log.key).await?;
+            if !issue.fields.subtasks.is_empty() {
+                println!(
+                    "Skipping {}: the time tracking of its sub-tasks {} cannot be backfilled",
+                    issue_log.key,
+                    issue.fields.subtasks.iter().map(|subtask| subtask.key.as_str()).join(", ")
+                );
+                continue;
+            }
+            let worklogs = jira_client.get_worklogs(&issue_log.key).await?;
+
+            let history = reconstruct_history(&first_collected, &issue, &worklogs);
+            inserted += database.insert_backfilled_issue_logs(history).await?;
+        }
+
+        println!("Inserted {} historical snapshots for epic {}", inserted, epic_key);
+    }
+
+    Ok(())
+}
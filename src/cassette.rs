@@ -0,0 +1,195 @@
+use crate::config::CassetteMode;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum CassetteError {
+    #[error("Could not read cassette {path}: {source}")]
+    Read {
+        path: String,
+        source: std::io::Error,
+    },
+    #[error("Could not parse cassette {path}: {source}")]
+    Parse {
+        path: String,
+        source: serde_json::Error,
+    },
+    #[error("Could not write cassette {path}: {source}")]
+    Write {
+        path: String,
+        source: std::io::Error,
+    },
+    #[error("No response recorded for {method} {path} in {dir}")]
+    NotRecorded {
+        method: String,
+        path: String,
+        dir: String,
+    },
+}
+
+/// Headers that carry credentials, replaced before an interaction is written.
+const SCRUBBED_HEADERS: [&str; 4] = [
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "set-cookie",
+];
+
+/// A request to Jira and the response it got, as stored in a cassette file.
+#[derive(Serialize, Deserialize)]
+pub struct Interaction {
+    pub method: String,
+    /// Path and query, without the base URL, so a cassette can be replayed against any host.
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_body: Option<serde_json::Value>,
+    pub request_headers: BTreeMap<String, String>,
+    pub status: u16,
+    pub response_headers: BTreeMap<String, String>,
+    /// JSON responses are kept as they are to keep cassettes readable, anything else as string.
+    pub response_body: serde_json::Value,
+}
+
+impl Interaction {
+    pub fn new(
+        method: &reqwest::Method,
+        path: &str,
+        request_body: Option<&serde_json::Value>,
+        request_headers: &reqwest::header::HeaderMap,
+        status: reqwest::StatusCode,
+        response_headers: &reqwest::header::HeaderMap,
+        response_body: &[u8],
+    ) -> Self {
+        Self {
+            method: method.to_string(),
+            path: path.to_string(),
+            request_body: request_body.cloned(),
+            request_headers: scrub(request_headers),
+            status: status.as_u16(),
+            response_headers: scrub(response_headers),
+            response_body: serde_json::from_slice(response_body).unwrap_or_else(|_| {
+                serde_json::Value::String(String::from_utf8_lossy(response_body).into_owned())
+            }),
+        }
+    }
+
+    pub fn status(&self) -> reqwest::StatusCode {
+        reqwest::StatusCode::from_u16(self.status)
+            .unwrap_or(reqwest::StatusCode::INTERNAL_SERVER_ERROR)
+    }
+
+    pub fn response_headers(&self) -> reqwest::header::HeaderMap {
+        self.response_headers
+            .iter()
+            .filter_map(|(name, value)| Some((name.parse().ok()?, value.parse().ok()?)))
+            .collect()
+    }
+
+    pub fn response_body(&self) -> Vec<u8> {
+        match &self.response_body {
+            serde_json::Value::String(text) => text.clone().into_bytes(),
+            body => serde_json::to_vec(body).unwrap_or_default(),
+        }
+    }
+}
+
+fn scrub(headers: &reqwest::header::HeaderMap) -> BTreeMap<String, String> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let value = if SCRUBBED_HEADERS.contains(&name.as_str()) {
+                "[scrubbed]".to_string()
+            } else {
+                String::from_utf8_lossy(value.as_bytes()).into_owned()
+            };
+            (name.to_string(), value)
+        })
+        .collect()
+}
+
+/// 64-bit FNV-1a, stable across Rust versions unlike the standard library hashers.
+fn fnv1a(parts: &[&[u8]]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for part in parts {
+        for byte in part.iter().chain(&[0]) {
+            hash ^= u64::from(*byte);
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    hash
+}
+
+/// A directory with one file per distinct request, to record the responses of Jira or to answer
+/// requests from them later.
+pub struct Cassette {
+    dir: PathBuf,
+    mode: CassetteMode,
+}
+
+impl Cassette {
+    /// `None` when cassettes are turned off.
+    pub fn new(dir: &str, mode: CassetteMode) -> Option<Self> {
+        (mode != CassetteMode::Off).then(|| Self {
+            dir: PathBuf::from(dir),
+            mode,
+        })
+    }
+
+    pub fn is_replay(&self) -> bool {
+        self.mode == CassetteMode::Replay
+    }
+
+    /// The file of a request, named after its method and a hash of its path and body, which
+    /// makes repeated requests overwrite each other so the last response is replayed.
+    fn file(&self, method: &str, path: &str, body: Option<&serde_json::Value>) -> PathBuf {
+        let body = body.map(|body| body.to_string()).unwrap_or_default();
+        let hash = fnv1a(&[method.as_bytes(), path.as_bytes(), body.as_bytes()]);
+        self.dir.join(format!("{}-{:016x}.json", method, hash))
+    }
+
+    pub fn replay(
+        &self,
+        method: &reqwest::Method,
+        path: &str,
+        body: Option<&serde_json::Value>,
+    ) -> Result<Interaction, CassetteError> {
+        let file = self.file(method.as_str(), path, body);
+        let content = match std::fs::read(&file) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(CassetteError::NotRecorded {
+                    method: method.to_string(),
+                    path: path.to_string(),
+                    dir: self.dir.display().to_string(),
+                })
+            }
+            Err(source) => {
+                return Err(CassetteError::Read {
+                    path: file.display().to_string(),
+                    source,
+                })
+            }
+        };
+        serde_json::from_slice(&content).map_err(|source| CassetteError::Parse {
+            path: file.display().to_string(),
+            source,
+        })
+    }
+
+    pub fn record(&self, interaction: &Interaction) -> Result<(), CassetteError> {
+        let file = self.file(
+            &interaction.method,
+            &interaction.path,
+            interaction.request_body.as_ref(),
+        );
+        let write_error = |source| CassetteError::Write {
+            path: file.display().to_string(),
+            source,
+        };
+        std::fs::create_dir_all(&self.dir).map_err(write_error)?;
+        let content = serde_json::to_vec_pretty(interaction).expect("Interactions are valid JSON");
+        std::fs::write(&file, content).map_err(write_error)
+    }
+}
@@ -0,0 +1,82 @@
+use clap::{Parser, Subcommand, ValueEnum};
+
+/// Tracks the health of Jira epics over time.
+#[derive(Parser)]
+#[command(version)]
+pub struct Cli {
+    /// Runs `serve` with the collector when no command is given.
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// Answer Jira requests from the fixture JSON in this directory instead of a real Jira, e.g.
+    /// to demo the dashboard. Set DATABASE_PATH to keep the demo data apart.
+    #[arg(long, global = true)]
+    pub fixtures: Option<std::path::PathBuf>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Serve the dashboard and API, collecting data from Jira in the background.
+    Serve {
+        /// Only serve the data already in the database, e.g. for a read-only replica.
+        #[arg(long)]
+        no_collector: bool,
+    },
+    /// Collect data from Jira once and exit, with a non-zero exit code on failure.
+    Collect {
+        /// Only refresh this epic and all of its issues.
+        #[arg(long)]
+        epic: Option<String>,
+    },
+    /// Write every stored issue snapshot to stdout or a file.
+    Export {
+        #[arg(long, value_enum, default_value_t = ExportFormat::Json)]
+        format: ExportFormat,
+        /// Only export the issues that have been part of this epic.
+        #[arg(long)]
+        epic: Option<String>,
+        /// File to write to instead of stdout.
+        #[arg(long, short)]
+        output: Option<std::path::PathBuf>,
+    },
+    /// Print the metrics and health of every epic.
+    Report {
+        #[arg(long, value_enum, default_value_t = ReportFormat::Text)]
+        format: ReportFormat,
+    },
+    /// Validate the configuration, including the Jira settings, and exit.
+    CheckConfig,
+    /// Check the connection to Jira, the configured queries and fields, and the database.
+    Doctor,
+    /// Reconstruct the history of issues from before they were first collected. Issues with
+    /// sub-tasks are skipped.
+    Backfill {
+        /// Only backfill the issues of this epic.
+        #[arg(long)]
+        epic: Option<String>,
+    },
+    /// Print the schema version of the database and whether migrations are pending.
+    SchemaVersion,
+}
+
+impl Command {
+    /// Whether the command talks to Jira and therefore needs its settings.
+    pub fn requires_jira(&self) -> bool {
+        match self {
+            Command::Serve { no_collector } => !no_collector,
+            Command::Collect { .. } | Command::CheckConfig | Command::Doctor | Command::Backfill { .. } => true,
+            Command::Export { .. } | Command::Report { .. } | Command::SchemaVersion => false,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+pub enum ExportFormat {
+    Json,
+    Csv,
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+pub enum ReportFormat {
+    Text,
+    Json,
+}
@@ -1,35 +1,307 @@
+use crate::config::Config;
 use crate::database::*;
 use crate::jira_client::*;
-use std::env;
+use crate::models::{IssueRemoval, RemovalReason, SyncRun};
+use itertools::Itertools;
+use std::collections::HashSet;
+use thiserror::Error;
 
-pub async fn collect_data(jira_client: &JiraClient, database: &Database) -> Result<(), String> {
-    println!("Collecting data...");
+#[derive(Error, Debug)]
+pub enum CollectorError {
+    #[error("Jira error: {0}")]
+    Jira(#[from] JiraError),
+    #[error("Database error: {0}")]
+    Database(#[from] DatabaseError),
+    #[error("Epic {0} does not match the epics JQL")]
+    EpicNotFound(String),
+}
+
+const LAST_SYNC_STATE: &str = "last_sync_at";
+const LAST_FULL_SYNC_STATE: &str = "last_full_sync_at";
+const SNAPSHOTS_COMPACTED_STATE: &str = "snapshots_compacted_at";
+// Jira only resolves `updated` to the minute, so re-request a few extra minutes
+// to not miss issues updated while the previous sync was running.
+const INCREMENTAL_SYNC_OVERLAP_MINUTES: i64 = 5;
+// Jira reads absolute dates in JQL in the time zone of the user, which can be up to 12 hours
+// behind UTC, so the cutoff is moved back that far to not miss issues in any time zone.
+const JQL_TIME_ZONE_MARGIN_HOURS: i64 = 12;
+
+async fn get_sync_timestamp(
+    database: &Database,
+    name: &str,
+) -> Result<Option<chrono::DateTime<chrono::Utc>>, CollectorError> {
+    let value = database.get_sync_state(name).await?;
+    Ok(value
+        .and_then(|value| chrono::DateTime::parse_from_rfc3339(&value).ok())
+        .map(|timestamp| timestamp.with_timezone(&chrono::Utc)))
+}
+
+/// Returns the moment issues have to be updated after to be fetched, or `None` when a full sweep is due.
+async fn get_incremental_sync_start(
+    database: &Database,
+    config: &Config,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<Option<chrono::DateTime<chrono::Utc>>, CollectorError> {
+    let last_sync = get_sync_timestamp(database, LAST_SYNC_STATE).await?;
+    let last_full_sync = get_sync_timestamp(database, LAST_FULL_SYNC_STATE).await?;
+
+    let full_sync_interval = chrono::Duration::hours(config.collector.full_sync_interval_hours);
+
+    match (last_sync, last_full_sync) {
+        (Some(last_sync), Some(last_full_sync)) if now - last_full_sync < full_sync_interval => {
+            Ok(Some(last_sync))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Removes the duplicate snapshots stored before change detection was introduced. Runs only once per database.
+pub async fn compact_snapshots_once(database: &Database) -> Result<(), CollectorError> {
+    if get_sync_timestamp(database, SNAPSHOTS_COMPACTED_STATE).await?.is_some() {
+        return Ok(());
+    }
+
+    println!("Compacting duplicate issue snapshots...");
+    let removed = database.compact_issue_snapshots().await?;
+    database
+        .set_sync_state(SNAPSHOTS_COMPACTED_STATE, &chrono::Utc::now().to_rfc3339())
+        .await?;
+    println!("Removed {} duplicate issue snapshots", removed);
+    Ok(())
+}
 
-    let epics_jql = env::var("JIRA_EPICS_JQL").expect("JIRA_EPICS_JQL must be set");
-    let issues_jql = env::var("JIRA_ISSUES_JQL").expect("JIRA_ISSUES_JQL must be set");
+/// Records a removal for every stored issue of the given epics that a full sync did not return,
+/// asking Jira for the issue to tell why. Returns the number of removed issues.
+async fn record_removed_issues(
+    jira_client: &JiraClient,
+    database: &Database,
+    epic_keys: &[String],
+    returned_keys: &HashSet<String>,
+) -> Result<usize, CollectorError> {
+    let removed_at = chrono::Utc::now().to_rfc3339();
+    let mut removals = Vec::new();
+
+    for log in database.get_all_latest_issue_logs().await? {
+        if !epic_keys.contains(&log.epic_key) || returned_keys.contains(&log.key) {
+            continue;
+        }
+        let reason = match jira_client.get_issue(&log.key).await {
+            Err(JiraError::NotFound(_)) => RemovalReason::Deleted,
+            Err(err) => return Err(err.into()),
+            Ok(issue) if issue.fields.epic_key.as_ref() != Some(&log.epic_key) => RemovalReason::MovedToOtherEpic,
+            Ok(_) => RemovalReason::NoLongerMatchesJql,
+        };
+        println!("Issue {} was removed from epic {}: {}", log.key, log.epic_key, reason.as_str());
+        removals.push(IssueRemoval {
+            key: log.key,
+            epic_key: log.epic_key,
+            removed_at: removed_at.clone(),
+            reason,
+        });
+    }
+
+    let removed = removals.len();
+    database.insert_issue_removals(removals).await?;
+    Ok(removed)
+}
+
+/// Collects data once and records the run in the database, including the error when it fails.
+/// With an `epic_key` only that epic and all of its issues are fetched. Returns the finished run.
+pub async fn collect_data(
+    jira_client: &JiraClient,
+    database: &Database,
+    config: &Config,
+    epic_key: Option<&str>,
+) -> Result<SyncRun, CollectorError> {
+    let sync_started_at = chrono::Utc::now();
+    let mut run = SyncRun {
+        id: 0,
+        started_at: sync_started_at.to_rfc3339(),
+        finished_at: None,
+        epic_key: epic_key.map(str::to_string),
+        full_sync: false,
+        epics_fetched: 0,
+        issues_fetched: 0,
+        snapshots_written: 0,
+        issues_removed: 0,
+        error: None,
+    };
+    run.id = database.insert_sync_run(&run).await?;
+
+    let result = match epic_key {
+        Some(epic_key) => sync_epic(jira_client, database, config, epic_key, &mut run).await,
+        None => sync(jira_client, database, config, sync_started_at, &mut run).await,
+    };
+
+    run.finished_at = Some(chrono::Utc::now().to_rfc3339());
+    run.error = result.as_ref().err().map(|e| e.to_string());
+    database.update_sync_run(&run).await?;
+    result?;
+    Ok(run)
+}
+
+/// Fetches the epics and their issues and stores what changed, counting what it did in `run`.
+async fn sync(
+    jira_client: &JiraClient,
+    database: &Database,
+    config: &Config,
+    sync_started_at: chrono::DateTime<chrono::Utc>,
+    run: &mut SyncRun,
+) -> Result<(), CollectorError> {
+    let updated_since = get_incremental_sync_start(database, config, sync_started_at).await?;
+    run.full_sync = updated_since.is_none();
+
+    match updated_since {
+        Some(updated_since) => println!("Collecting data updated since {}...", updated_since),
+        None => println!("Collecting data (full sync)..."),
+    }
+
+    let epics_jql = &config.jira.epics_jql;
 
     let epics = jira_client
         .get_jql(&format!("{} AND type = Epic", epics_jql))
-        .await
-        .expect("Could not get epics");
-    let epics_csv = epics
+        .await?;
+    let epic_keys = epics
         .iter()
-        .map(|epic| format!("{}", epic.key))
-        .collect::<Vec<String>>()
-        .join(",");
+        .map(|epic| epic.key.clone())
+        .collect::<Vec<String>>();
+    run.epics_fetched = epics.len() as i64;
+    let stored_epic_keys = database
+        .get_epics()
+        .await?
+        .into_iter()
+        .map(|epic| epic.key)
+        .collect::<HashSet<_>>();
 
     for epic in epics {
         println!("Epic {}: {}", epic.key, epic.fields.summary);
+        database.insert_epic(&epic).await?;
+    }
+    database
+        .archive_epics_except(epic_keys.clone(), &sync_started_at.to_rfc3339())
+        .await?;
+
+    match updated_since {
+        None => store_issues(jira_client, database, config, &epic_keys, None, run).await?,
+        Some(updated_since) => {
+            // Epics that only started matching the epics JQL in this run have none of their
+            // issues stored yet, so all of them are fetched regardless of when they were updated.
+            let (new_epic_keys, known_epic_keys): (Vec<_>, Vec<_>) = epic_keys
+                .iter()
+                .cloned()
+                .partition(|epic_key| !stored_epic_keys.contains(epic_key));
+            if !known_epic_keys.is_empty() {
+                // An absolute cutoff derived from the stored sync state, unlike a relative one,
+                // gives the same query when a cassette of the sync is replayed later.
+                let updated_after = updated_since
+                    - chrono::Duration::minutes(INCREMENTAL_SYNC_OVERLAP_MINUTES)
+                    - chrono::Duration::hours(JQL_TIME_ZONE_MARGIN_HOURS);
+                store_issues(jira_client, database, config, &known_epic_keys, Some(updated_after), run).await?;
+            }
+            if !new_epic_keys.is_empty() {
+                println!("Collecting all issues of new epics {}", new_epic_keys.join(", "));
+                store_issues(jira_client, database, config, &new_epic_keys, None, run).await?;
+            }
+        }
+    }
+
+    // Unchanged issues are not returned by an incremental sync but are still part of their epics.
+    if let Some(updated_since) = updated_since {
         database
-            .insert_epic(&epic)
-            .await
-            .expect("Could not insert epic");
+            .touch_issues_seen_since(&updated_since.to_rfc3339(), &chrono::Utc::now().to_rfc3339())
+            .await?;
+    }
+
+    let sync_started_at = sync_started_at.to_rfc3339();
+    database
+        .set_sync_state(LAST_SYNC_STATE, &sync_started_at)
+        .await?;
+    if updated_since.is_none() {
+        database
+            .set_sync_state(LAST_FULL_SYNC_STATE, &sync_started_at)
+            .await?;
+    }
+
+    println!("Data collected and inserted into database");
+    Ok(())
+}
+
+/// Refreshes a single epic and all of its issues, leaving the state of the regular syncs alone.
+async fn sync_epic(
+    jira_client: &JiraClient,
+    database: &Database,
+    config: &Config,
+    epic_key: &str,
+    run: &mut SyncRun,
+) -> Result<(), CollectorError> {
+    println!("Collecting data of epic {}...", epic_key);
+    run.full_sync = true;
+
+    let epics = jira_client
+        .get_jql(&format!("{} AND type = Epic AND key = \"{}\"", config.jira.epics_jql, epic_key))
+        .await?;
+    let Some(epic) = epics.first() else {
+        return Err(CollectorError::EpicNotFound(epic_key.to_string()));
+    };
+    run.epics_fetched = 1;
+    println!("Epic {}: {}", epic.key, epic.fields.summary);
+    database.insert_epic(epic).await?;
+
+    let epic_keys = vec![epic.key.clone()];
+    store_issues(jira_client, database, config, &epic_keys, None, run).await?;
+
+    println!("Data of epic {} collected and inserted into database", epic_key);
+    Ok(())
+}
+
+/// Fetches the issues of the epics matching the issues JQL, only those updated after
+/// `updated_after` if given, and stores their changed snapshots and the sprints they are in.
+/// Without `updated_after` every issue of the epics is returned, so stored issues that are not
+/// are recorded as removed.
+async fn store_issues(
+    jira_client: &JiraClient,
+    database: &Database,
+    config: &Config,
+    epic_keys: &[String],
+    updated_after: Option<chrono::DateTime<chrono::Utc>>,
+    run: &mut SyncRun,
+) -> Result<(), CollectorError> {
+    // Without any epics there are no issues to fetch, and `IN ()` is not valid JQL anyway.
+    let issues = if epic_keys.is_empty() {
+        Vec::new()
+    } else {
+        let mut jql = format!("{} AND {}", config.jira.issues_jql, jira_client.epic_link_jql(epic_keys).await?);
+        if let Some(updated_after) = updated_after {
+            jql.push_str(&format!(" AND updated >= \"{}\"", updated_after.format("%Y/%m/%d %H:%M")));
+        }
+        jira_client.get_jql(&jql).await?
+    };
+    run.issues_fetched += issues.len() as i64;
+
+    // Only a full sync returns every issue, an incremental one leaves out the unchanged ones.
+    if updated_after.is_none() {
+        let returned_keys = issues.iter().map(|issue| issue.key.clone()).collect::<HashSet<_>>();
+        let removed = record_removed_issues(jira_client, database, epic_keys, &returned_keys).await?;
+        println!("{} issues removed", removed);
+        run.issues_removed += removed as i64;
     }
 
-    let issues = jira_client
-        .get_jql(&format!("{} AND 'Epic Link' IN ({})", issues_jql, epics_csv))
-        .await
-        .expect("Could not get issues");
+    let mut sprints = issues
+        .iter()
+        .flat_map(|issue| issue.fields.sprints.iter().cloned())
+        .collect::<Vec<_>>();
+    for board_id in &config.jira.board_ids {
+        sprints.extend(jira_client.get_board_sprints(*board_id).await?);
+    }
+    // Board sprints come last and take precedence over the copies in the sprint field.
+    let sprints = sprints
+        .into_iter()
+        .rev()
+        .unique_by(|sprint| sprint.id)
+        .collect::<Vec<_>>();
+    println!("Updating {} sprints", sprints.len());
+    database.upsert_sprints(sprints).await?;
+
     for issue in issues {
         println!(
             "Collecting issue {}: {}, {}, {}, {}",
@@ -41,15 +313,13 @@ pub async fn collect_data(jira_client: &JiraClient, database: &Database) -> Resu
                 .fields
                 .assignee
                 .as_ref()
-                .map(|a| a.name.clone())
+                .map(|a| a.identifier())
                 .unwrap_or("unassigned".to_string())
         );
-        database
-            .insert_issue(&issue)
-            .await
-            .expect("Could not insert issue");
+        if database.insert_issue(&issue).await? {
+            run.snapshots_written += 1;
+        }
     }
-
-    println!("Data collected and inserted into database");
+    println!("{} issue snapshots changed", run.snapshots_written);
     Ok(())
 }
@@ -0,0 +1,490 @@
+use crate::health::HealthRules;
+use crate::metrics::Metric;
+use serde::Deserialize;
+use std::env;
+use std::fmt;
+use std::str::FromStr;
+
+const DEFAULT_CONFIG_PATH: &str = "config.toml";
+
+#[derive(Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub jira: JiraConfig,
+    pub collector: CollectorConfig,
+    pub webhook: WebhookConfig,
+    pub database: DatabaseConfig,
+    pub graphs: GraphsConfig,
+    pub health: HealthRules,
+    pub forecast: ForecastConfig,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub bind_address: String,
+    pub static_dir: String,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind_address: "0.0.0.0:8080".to_string(),
+            static_dir: "src/static".to_string(),
+        }
+    }
+}
+
+/// How requests to Jira are authenticated.
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthMode {
+    /// Personal access token of Jira Server/Data Center, sent as `Bearer` token.
+    Bearer,
+    /// Email address and API token of Jira Cloud.
+    Basic,
+    /// OAuth 2.0 client credentials, exchanged for short-lived access tokens.
+    OAuth2,
+}
+
+impl FromStr for AuthMode {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "bearer" => Ok(AuthMode::Bearer),
+            "basic" => Ok(AuthMode::Basic),
+            "oauth2" => Ok(AuthMode::OAuth2),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Whether requests to Jira and their responses are recorded to a cassette directory, or answered
+/// from one without contacting Jira.
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum CassetteMode {
+    Off,
+    Record,
+    Replay,
+}
+
+impl FromStr for CassetteMode {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "off" => Ok(CassetteMode::Off),
+            "record" => Ok(CassetteMode::Record),
+            "replay" => Ok(CassetteMode::Replay),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct JiraConfig {
+    pub base_url: String,
+    /// REST API version, 2 for Jira Server/Data Center or 3 for Jira Cloud.
+    pub api_version: u32,
+    pub auth: AuthMode,
+    pub username: String,
+    pub token: String,
+    pub oauth_client_id: String,
+    pub oauth_client_secret: String,
+    pub oauth_token_url: String,
+    pub epics_jql: String,
+    pub issues_jql: String,
+    /// How issues are linked to epics: a custom field ID like `customfield_10014`, the name of
+    /// the field, which is looked up in Jira, or `parent` for the issue hierarchy.
+    pub epic_link_field: String,
+    /// Custom field ID or name of the story points field, empty to not track story points.
+    pub story_points_field: String,
+    /// Custom field ID or name of the sprint field, empty to not track sprints.
+    pub sprint_field: String,
+    /// Agile boards whose sprints are fetched, so sprints without collected issues are known too.
+    pub board_ids: Vec<u64>,
+    pub max_retries: u32,
+    pub retry_base_delay_ms: u64,
+    pub retry_max_delay_ms: u64,
+    pub cassette_mode: CassetteMode,
+    /// Directory cassettes are recorded to or replayed from.
+    pub cassette_dir: String,
+}
+
+impl Default for JiraConfig {
+    fn default() -> Self {
+        Self {
+            base_url: String::new(),
+            api_version: 2,
+            auth: AuthMode::Bearer,
+            username: String::new(),
+            token: String::new(),
+            oauth_client_id: String::new(),
+            oauth_client_secret: String::new(),
+            oauth_token_url: "https://auth.atlassian.com/oauth/token".to_string(),
+            epics_jql: String::new(),
+            issues_jql: String::new(),
+            epic_link_field: "Epic Link".to_string(),
+            story_points_field: String::new(),
+            sprint_field: "Sprint".to_string(),
+            board_ids: Vec::new(),
+            max_retries: 3,
+            retry_base_delay_ms: 1000,
+            retry_max_delay_ms: 60_000,
+            cassette_mode: CassetteMode::Off,
+            cassette_dir: String::new(),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct CollectorConfig {
+    pub interval_secs: u64,
+    pub full_sync_interval_hours: i64,
+}
+
+impl Default for CollectorConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: 10 * 60,
+            full_sync_interval_hours: 24,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ForecastConfig {
+    /// Trailing window of days the burn rate of an epic is measured over.
+    pub window_days: i64,
+    /// Trailing days of logged time the Monte Carlo forecast samples daily throughput from.
+    pub history_days: i64,
+    /// Number of simulated runs of the Monte Carlo forecast.
+    pub simulations: usize,
+    /// Seed of the simulations, fixed so the same snapshots always give the same dates.
+    pub seed: u64,
+}
+
+impl Default for ForecastConfig {
+    fn default() -> Self {
+        Self {
+            window_days: 14,
+            history_days: 28,
+            simulations: 1000,
+            seed: 1,
+        }
+    }
+}
+
+/// Jira webhooks are only accepted when a secret is set.
+#[derive(Deserialize, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebhookConfig {
+    pub secret: String,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    pub path: String,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            path: "storage/jira_health_checker.db".to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct GraphSize {
+    pub width: i32,
+    pub height: i32,
+    pub margin: i32,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct GraphsConfig {
+    pub issue: GraphSize,
+    pub epic: GraphSize,
+    /// The pair of metrics plotted unless a graph is requested with `?metrics=`.
+    pub metrics: [Metric; 2],
+}
+
+impl Default for GraphsConfig {
+    fn default() -> Self {
+        Self {
+            issue: GraphSize {
+                width: 150,
+                height: 50,
+                margin: 10,
+            },
+            epic: GraphSize {
+                width: 600,
+                height: 200,
+                margin: 10,
+            },
+            metrics: [Metric::OriginalEstimate, Metric::TimeSpent],
+        }
+    }
+}
+
+/// Every problem found while loading the configuration, so they can all be fixed at once.
+#[derive(Debug)]
+pub struct ConfigError {
+    pub problems: Vec<String>,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Invalid configuration:")?;
+        for problem in &self.problems {
+            writeln!(f, "  - {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+fn override_from_env<T: FromStr>(value: &mut T, name: &str, problems: &mut Vec<String>) {
+    if let Ok(raw) = env::var(name) {
+        match raw.parse::<T>() {
+            Ok(parsed) => *value = parsed,
+            Err(_) => problems.push(format!("{} has an invalid value: {:?}", name, raw)),
+        }
+    }
+}
+
+impl Config {
+    /// Loads the TOML file at `CONFIG_PATH` (default `config.toml`, which may be absent),
+    /// applies environment variable overrides and validates the result. Jira credentials and
+    /// queries are only required when `require_jira` is set.
+    pub fn load(require_jira: bool) -> Result<Self, ConfigError> {
+        let mut problems = Vec::new();
+
+        let path = env::var("CONFIG_PATH").ok();
+        let mut config = match std::fs::read_to_string(path.as_deref().unwrap_or(DEFAULT_CONFIG_PATH)) {
+            Ok(content) => toml::from_str::<Config>(&content).unwrap_or_else(|e| {
+                problems.push(format!("Could not parse configuration file: {}", e));
+                Config::default()
+            }),
+            Err(e) if path.is_none() && e.kind() == std::io::ErrorKind::NotFound => Config::default(),
+            Err(e) => {
+                problems.push(format!(
+                    "Could not read configuration file {}: {}",
+                    path.as_deref().unwrap_or(DEFAULT_CONFIG_PATH),
+                    e
+                ));
+                Config::default()
+            }
+        };
+
+        config.apply_env_overrides(&mut problems);
+        problems.extend(config.validate(require_jira));
+
+        if problems.is_empty() {
+            Ok(config)
+        } else {
+            Err(ConfigError { problems })
+        }
+    }
+
+    fn apply_env_overrides(&mut self, problems: &mut Vec<String>) {
+        override_from_env(&mut self.server.bind_address, "SERVER_BIND_ADDRESS", problems);
+        override_from_env(&mut self.server.static_dir, "SERVER_STATIC_DIR", problems);
+
+        override_from_env(&mut self.jira.base_url, "JIRA_BASE_URL", problems);
+        override_from_env(&mut self.jira.api_version, "JIRA_API_VERSION", problems);
+        override_from_env(&mut self.jira.auth, "JIRA_AUTH", problems);
+        override_from_env(&mut self.jira.username, "JIRA_USERNAME", problems);
+        override_from_env(&mut self.jira.token, "JIRA_TOKEN", problems);
+        override_from_env(&mut self.jira.oauth_client_id, "JIRA_OAUTH_CLIENT_ID", problems);
+        override_from_env(&mut self.jira.oauth_client_secret, "JIRA_OAUTH_CLIENT_SECRET", problems);
+        override_from_env(&mut self.jira.oauth_token_url, "JIRA_OAUTH_TOKEN_URL", problems);
+        override_from_env(&mut self.jira.epics_jql, "JIRA_EPICS_JQL", problems);
+        override_from_env(&mut self.jira.issues_jql, "JIRA_ISSUES_JQL", problems);
+        override_from_env(&mut self.jira.epic_link_field, "JIRA_EPIC_LINK_FIELD", problems);
+        override_from_env(&mut self.jira.story_points_field, "JIRA_STORY_POINTS_FIELD", problems);
+        override_from_env(&mut self.jira.sprint_field, "JIRA_SPRINT_FIELD", problems);
+        override_from_env(&mut self.jira.max_retries, "JIRA_MAX_RETRIES", problems);
+        override_from_env(&mut self.jira.retry_base_delay_ms, "JIRA_RETRY_BASE_DELAY_MS", problems);
+        override_from_env(&mut self.jira.retry_max_delay_ms, "JIRA_RETRY_MAX_DELAY_MS", problems);
+        override_from_env(&mut self.jira.cassette_mode, "JIRA_CASSETTE_MODE", problems);
+        override_from_env(&mut self.jira.cassette_dir, "JIRA_CASSETTE_DIR", problems);
+
+        override_from_env(&mut self.collector.interval_secs, "COLLECTOR_INTERVAL_SECS", problems);
+        override_from_env(
+            &mut self.collector.full_sync_interval_hours,
+            "JIRA_FULL_SYNC_INTERVAL_HOURS",
+            problems,
+        );
+
+        override_from_env(&mut self.webhook.secret, "WEBHOOK_SECRET", problems);
+
+        override_from_env(&mut self.database.path, "DATABASE_PATH", problems);
+
+        let health = &mut self.health;
+        override_from_env(&mut health.overrun_amber_percent, "HEALTH_OVERRUN_AMBER_PERCENT", problems);
+        override_from_env(&mut health.overrun_red_percent, "HEALTH_OVERRUN_RED_PERCENT", problems);
+        override_from_env(&mut health.unestimated_amber_count, "HEALTH_UNESTIMATED_AMBER_COUNT", problems);
+        override_from_env(&mut health.unestimated_red_percent, "HEALTH_UNESTIMATED_RED_PERCENT", problems);
+        override_from_env(&mut health.stale_amber_days, "HEALTH_STALE_AMBER_DAYS", problems);
+        override_from_env(&mut health.stale_red_days, "HEALTH_STALE_RED_DAYS", problems);
+        override_from_env(
+            &mut health.estimate_growth_amber_percent,
+            "HEALTH_ESTIMATE_GROWTH_AMBER_PERCENT",
+            problems,
+        );
+        override_from_env(
+            &mut health.estimate_growth_red_percent,
+            "HEALTH_ESTIMATE_GROWTH_RED_PERCENT",
+            problems,
+        );
+        override_from_env(&mut health.ignore_done_issues, "HEALTH_IGNORE_DONE_ISSUES", problems);
+
+        override_from_env(&mut self.forecast.window_days, "FORECAST_WINDOW_DAYS", problems);
+        override_from_env(&mut self.forecast.history_days, "FORECAST_HISTORY_DAYS", problems);
+        override_from_env(&mut self.forecast.simulations, "FORECAST_SIMULATIONS", problems);
+        override_from_env(&mut self.forecast.seed, "FORECAST_SEED", problems);
+    }
+
+    /// Settings only needed by commands that talk to Jira.
+    fn validate_jira_access(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        // Replayed responses need the same queries, but neither the address nor the credentials.
+        if self.jira.cassette_mode != CassetteMode::Replay {
+            if self.jira.base_url.is_empty() {
+                problems.push("jira.base_url (JIRA_BASE_URL) must be set".to_string());
+            } else if !self.jira.base_url.starts_with("http://") && !self.jira.base_url.starts_with("https://") {
+                problems.push(format!(
+                    "jira.base_url must start with http:// or https://, got {:?}",
+                    self.jira.base_url
+                ));
+            }
+            match self.jira.auth {
+                AuthMode::Bearer => {
+                    if self.jira.token.is_empty() {
+                        problems.push("jira.token (JIRA_TOKEN) must be set".to_string());
+                    }
+                }
+                AuthMode::Basic => {
+                    if self.jira.username.is_empty() {
+                        problems.push("jira.username (JIRA_USERNAME) must be set for basic auth".to_string());
+                    }
+                    if self.jira.token.is_empty() {
+                        problems.push("jira.token (JIRA_TOKEN) must be set to an API token for basic auth".to_string());
+                    }
+                }
+                AuthMode::OAuth2 => {
+                    if self.jira.oauth_client_id.is_empty() {
+                        problems.push("jira.oauth_client_id (JIRA_OAUTH_CLIENT_ID) must be set for oauth2".to_string());
+                    }
+                    if self.jira.oauth_client_secret.is_empty() {
+                        problems.push(
+                            "jira.oauth_client_secret (JIRA_OAUTH_CLIENT_SECRET) must be set for oauth2".to_string(),
+                        );
+                    }
+                    if !self.jira.oauth_token_url.starts_with("http://")
+                        && !self.jira.oauth_token_url.starts_with("https://")
+                    {
+                        problems.push(format!(
+                            "jira.oauth_token_url must start with http:// or https://, got {:?}",
+                            self.jira.oauth_token_url
+                        ));
+                    }
+                }
+            }
+        }
+        if self.jira.epics_jql.trim().is_empty() {
+            problems.push("jira.epics_jql (JIRA_EPICS_JQL) must be set".to_string());
+        }
+        if self.jira.issues_jql.trim().is_empty() {
+            problems.push("jira.issues_jql (JIRA_ISSUES_JQL) must be set".to_string());
+        }
+
+        problems
+    }
+
+    fn validate(&self, require_jira: bool) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        if self.server.bind_address.parse::<std::net::SocketAddr>().is_err() {
+            problems.push(format!(
+                "server.bind_address must be an address like 0.0.0.0:8080, got {:?}",
+                self.server.bind_address
+            ));
+        }
+
+        if require_jira {
+            problems.extend(self.validate_jira_access());
+        }
+        if !matches!(self.jira.api_version, 2 | 3) {
+            problems.push(format!("jira.api_version must be 2 or 3, got {}", self.jira.api_version));
+        }
+        if self.jira.epic_link_field.is_empty() {
+            problems.push("jira.epic_link_field must not be empty".to_string());
+        }
+        if self.jira.cassette_mode != CassetteMode::Off && self.jira.cassette_dir.is_empty() {
+            problems.push("jira.cassette_dir (JIRA_CASSETTE_DIR) must be set to record or replay".to_string());
+        }
+        if self.jira.retry_base_delay_ms > self.jira.retry_max_delay_ms {
+            problems.push("jira.retry_base_delay_ms must not exceed jira.retry_max_delay_ms".to_string());
+        }
+
+        if self.collector.interval_secs == 0 {
+            problems.push("collector.interval_secs must be greater than 0".to_string());
+        }
+        if self.collector.full_sync_interval_hours <= 0 {
+            problems.push("collector.full_sync_interval_hours must be greater than 0".to_string());
+        }
+
+        if self.database.path.is_empty() {
+            problems.push("database.path must not be empty".to_string());
+        }
+
+        for (name, size) in [("graphs.issue", &self.graphs.issue), ("graphs.epic", &self.graphs.epic)] {
+            if size.width <= 0 || size.height <= 0 || size.margin < 0 {
+                problems.push(format!(
+                    "{} must have a positive width and height and a non-negative margin",
+                    name
+                ));
+            }
+        }
+
+        let health = &self.health;
+        if health.overrun_red_percent < health.overrun_amber_percent {
+            problems.push("health.overrun_red_percent must not be below health.overrun_amber_percent".to_string());
+        }
+        if health.stale_red_days < health.stale_amber_days {
+            problems.push("health.stale_red_days must not be below health.stale_amber_days".to_string());
+        }
+        if health.estimate_growth_red_percent < health.estimate_growth_amber_percent {
+            problems.push(
+                "health.estimate_growth_red_percent must not be below health.estimate_growth_amber_percent"
+                    .to_string(),
+            );
+        }
+
+        if self.forecast.window_days <= 0 {
+            problems.push("forecast.window_days must be greater than 0".to_string());
+        }
+        if self.forecast.history_days <= 0 {
+            problems.push("forecast.history_days must be greater than 0".to_string());
+        }
+        if self.forecast.simulations == 0 {
+            problems.push("forecast.simulations must be greater than 0".to_string());
+        }
+
+        problems
+    }
+}
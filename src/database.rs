@@ -1,4 +1,7 @@
+use crate::config::DatabaseConfig;
+use crate::migrations;
 use crate::models::*;
+use rusqlite::OptionalExtension;
 use std::fs;
 use thiserror::Error;
 
@@ -8,6 +11,8 @@ pub enum DatabaseError {
     StorageCreation(#[from] std::io::Error),
     #[error("SQLite error: {0}")]
     Sqlite(String),
+    #[error("Database schema version {0} is newer than the latest known version {1}")]
+    SchemaTooNew(i64, i64),
     #[error("Failed to parse count: {0}")]
     ParseError(#[from] std::num::ParseIntError),
 }
@@ -24,61 +29,201 @@ impl From<rusqlite::Error> for DatabaseError {
     }
 }
 
+fn issue_log_from_row(row: &rusqlite::Row) -> rusqlite::Result<IssueLog> {
+    Ok(IssueLog {
+        key: row.get("key")?,
+        summary: row.get("summary")?,
+        epic_key: row.get("epic_key")?,
+        time_estimate: row.get("time_estimate")?,
+        time_spent: row.get("time_spent")?,
+        remaining_estimate: row.get("remaining_estimate")?,
+        story_points: row.get("story_points")?,
+        updated_at: row.get("updated_at")?,
+        assignee: row.get("assignee")?,
+        status: row.get("status")?,
+        status_category: row.get("status_category")?,
+        issue_type: row.get("issue_type")?,
+        priority: row.get("priority")?,
+        resolution: row.get("resolution")?,
+        resolution_date: row.get("resolution_date")?,
+        labels: json_column(row, "labels")?,
+        sprint_ids: json_column(row, "sprint_ids")?,
+    })
+}
+
+fn json_column<T: serde::de::DeserializeOwned>(row: &rusqlite::Row, column: &str) -> rusqlite::Result<T> {
+    serde_json::from_str(&row.get::<_, String>(column)?).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e))
+    })
+}
+
+fn sprint_from_row(row: &rusqlite::Row) -> rusqlite::Result<Sprint> {
+    Ok(Sprint {
+        id: row.get("id")?,
+        name: row.get("name")?,
+        state: row.get("state")?,
+        start_date: row.get("start_date")?,
+        end_date: row.get("end_date")?,
+        complete_date: row.get("complete_date")?,
+        board_id: row.get("board_id")?,
+    })
+}
+
+fn removal_from_row(row: &rusqlite::Row) -> rusqlite::Result<IssueRemoval> {
+    Ok(IssueRemoval {
+        key: row.get("key")?,
+        epic_key: row.get("epic_key")?,
+        removed_at: row.get("removed_at")?,
+        reason: row.get::<_, String>("reason")?.parse().map_err(|e: String| {
+            rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, e.into())
+        })?,
+    })
+}
+
+fn sync_run_from_row(row: &rusqlite::Row) -> rusqlite::Result<SyncRun> {
+    Ok(SyncRun {
+        id: row.get("id")?,
+        started_at: row.get("started_at")?,
+        finished_at: row.get("finished_at")?,
+        epic_key: row.get("epic_key")?,
+        full_sync: row.get("full_sync")?,
+        epics_fetched: row.get("epics_fetched")?,
+        issues_fetched: row.get("issues_fetched")?,
+        snapshots_written: row.get("snapshots_written")?,
+        issues_removed: row.get("issues_removed")?,
+        error: row.get("error")?,
+    })
+}
+
+const INSERT_ISSUE_LOG_COLUMNS: &str = "key, summary, epic_key, time_estimate, time_spent, updated_at, assignee,
+    status, status_category, issue_type, priority, resolution, resolution_date, labels,
+    remaining_estimate, story_points, sprint_ids";
+
+fn insert_issue_log(statement: &str, tx: &rusqlite::Transaction, log: &IssueLog) -> rusqlite::Result<usize> {
+    let labels = serde_json::to_string(&log.labels).expect("Labels are always serializable");
+    let sprint_ids = serde_json::to_string(&log.sprint_ids).expect("Sprint IDs are always serializable");
+    tx.execute(
+        statement,
+        rusqlite::params![
+            log.key,
+            log.summary,
+            log.epic_key,
+            log.time_estimate,
+            log.time_spent,
+            log.updated_at,
+            log.assignee,
+            log.status,
+            log.status_category,
+            log.issue_type,
+            log.priority,
+            log.resolution,
+            log.resolution_date,
+            labels,
+            log.remaining_estimate,
+            log.story_points,
+            sprint_ids,
+        ],
+    )
+}
+
 pub struct Database {
     connection: tokio_rusqlite::Connection,
 }
 
 impl Database {
-    pub async fn new() -> Result<Self, DatabaseError> {
-        if !std::path::Path::new("storage/").exists() {
-            fs::create_dir("storage")?;
-        }
-        let connection = tokio_rusqlite::Connection::open("storage/jira_health_checker.db").await?;
-        let db = Database { connection };
-        db._init_database().await?;
+    pub async fn new(config: &DatabaseConfig) -> Result<Self, DatabaseError> {
+        let db = Self::open(config).await?;
+        db._migrate().await?;
         Ok(db)
     }
 
-    async fn _init_database(&self) -> Result<(), DatabaseError> {
-        let check_if_tables_exist = "
-            SELECT COUNT(*) 
-            FROM sqlite_schema 
-            WHERE type = 'table' AND name IN ('epics', 'issues')";
+    /// Opens the database without applying pending migrations.
+    pub async fn open(config: &DatabaseConfig) -> Result<Self, DatabaseError> {
+        if let Some(directory) = std::path::Path::new(&config.path).parent() {
+            if !directory.as_os_str().is_empty() && !directory.exists() {
+                fs::create_dir_all(directory)?;
+            }
+        }
+        let connection = tokio_rusqlite::Connection::open(&config.path).await?;
+        Ok(Database { connection })
+    }
+
+    async fn _migrate(&self) -> Result<(), DatabaseError> {
+        let current = self.get_schema_version().await?;
+        let latest = migrations::latest_version();
+        if current > latest {
+            return Err(DatabaseError::SchemaTooNew(current, latest));
+        }
 
-        let result = self
+        let applied = self
             .connection
+            .call(|conn| migrations::apply_migrations(conn).map_err(tokio_rusqlite::Error::Rusqlite))
+            .await?;
+        for version in applied {
+            println!("Applied database migration {}", version);
+        }
+        Ok(())
+    }
+
+    pub async fn get_schema_version(&self) -> Result<i64, DatabaseError> {
+        let version = self
+            .connection
+            .call(|conn| migrations::current_version(conn).map_err(tokio_rusqlite::Error::Rusqlite))
+            .await?;
+        Ok(version)
+    }
+
+    /// Checks that the database accepts writes, without changing anything.
+    pub async fn check_writable(&self) -> Result<(), DatabaseError> {
+        self.connection
             .call(|conn| {
-                let mut statement = conn.prepare(check_if_tables_exist)?;
-                statement
-                    .query_row([], |row| row.get::<_, i64>(0))
+                let tx = conn
+                    .transaction_with_behavior(rusqlite::TransactionBehavior::Immediate)
+                    .map_err(tokio_rusqlite::Error::Rusqlite)?;
+                tx.execute("CREATE TABLE write_check (id INTEGER)", [])
+                    .map_err(tokio_rusqlite::Error::Rusqlite)?;
+                tx.rollback().map_err(tokio_rusqlite::Error::Rusqlite)
+            })
+            .await?;
+        Ok(())
+    }
+
+    pub async fn get_sync_state(&self, name: &str) -> Result<Option<String>, DatabaseError> {
+        let query = "
+            SELECT value FROM sync_state WHERE name = ?
+        ";
+        let name = name.to_string();
+        let value = self
+            .connection
+            .call(move |conn| {
+                let mut stmt = conn
+                    .prepare_cached(query)
+                    .map_err(tokio_rusqlite::Error::Rusqlite)?;
+
+                stmt.query_row([name.as_str()], |row| row.get::<_, String>(0))
+                    .optional()
                     .map_err(tokio_rusqlite::Error::Rusqlite)
             })
             .await?;
+        Ok(value)
+    }
 
-        if result == 0 {
-            let init = "
-                CREATE TABLE IF NOT EXISTS epics (
-                    key TEXT NOT NULL PRIMARY KEY,
-                    summary TEXT NOT NULL,
-                    updated_at DATETIME NOT NULL
-                );
-                CREATE TABLE IF NOT EXISTS issues (
-                    key TEXT NOT NULL,
-                    summary TEXT NOT NULL,
-                    epic_key TEXT NOT NULL,
-                    time_estimate INTEGER NOT NULL,
-                    time_spent INTEGER NOT NULL,
-                    updated_at DATETIME NOT NULL,
-                    assignee TEXT NOT NULL,
-                    FOREIGN KEY (epic_key) REFERENCES epics(key)
-                )";
-            self.connection
-                .call(|conn| {
-                    conn.execute(init, [])
-                        .map_err(tokio_rusqlite::Error::Rusqlite)
-                })
-                .await?;
-        }
+    pub async fn set_sync_state(&self, name: &str, value: &str) -> Result<(), DatabaseError> {
+        let query = "
+            INSERT INTO sync_state (name, value)
+            VALUES (?, ?)
+            ON CONFLICT(name) DO UPDATE SET
+                value = excluded.value
+        ";
+        let name = name.to_string();
+        let value = value.to_string();
+
+        self.connection
+            .call(move |conn| {
+                conn.execute(query, (name.as_str(), value.as_str()))
+                    .map_err(tokio_rusqlite::Error::Rusqlite)
+            })
+            .await?;
         Ok(())
     }
 
@@ -88,7 +233,8 @@ impl Database {
             VALUES (?, ?, ?)
             ON CONFLICT(key) DO UPDATE SET
                 summary = excluded.summary,
-                updated_at = excluded.updated_at
+                updated_at = excluded.updated_at,
+                archived_at = NULL
         ";
         let key = epic.key.clone();
         let summary = epic.fields.summary.clone();
@@ -103,49 +249,489 @@ impl Database {
         Ok(())
     }
 
-    pub async fn insert_issue(&self, issue: &JiraIssue) -> Result<(), DatabaseError> {
+    /// Stores a new snapshot of the issue only if it differs from the latest stored one.
+    /// Either way the issue is marked as seen now. Returns whether a snapshot was written.
+    pub async fn insert_issue(&self, issue: &JiraIssue) -> Result<bool, DatabaseError> {
+        let latest_query = "
+            SELECT *
+            FROM issues
+            WHERE key = ?
+            ORDER BY updated_at DESC
+            LIMIT 1
+        ";
+        let insert_query = format!(
+            "INSERT INTO issues ({}) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            INSERT_ISSUE_LOG_COLUMNS
+        );
+        let last_removed_query = "
+            SELECT MAX(removed_at) FROM issue_removals WHERE key = ?
+        ";
+        let last_seen_query = "
+            INSERT INTO issue_last_seen (key, last_seen_at)
+            VALUES (?, ?)
+            ON CONFLICT(key) DO UPDATE SET
+                last_seen_at = excluded.last_seen_at
+        ";
+        let Some(epic_key) = issue.fields.epic_key.clone() else {
+            println!("Skipping issue {}: it is not linked to an epic", issue.key);
+            return Ok(false);
+        };
+        let fields = &issue.fields;
+        let name = |named: &Option<JiraNamed>| named.as_ref().map(|named| named.name.clone()).unwrap_or_default();
+        let current = IssueLog {
+            key: issue.key.clone(),
+            summary: fields.summary.clone(),
+            epic_key,
+            time_estimate: fields.time_estimate.unwrap_or(0) as i64,
+            time_spent: fields.time_spent.unwrap_or(0) as i64,
+            remaining_estimate: fields.remaining_estimate.unwrap_or(0) as i64,
+            story_points: fields.story_points,
+            updated_at: chrono::Utc::now().to_rfc3339(),
+            assignee: fields
+                .assignee
+                .as_ref()
+                .map(|assignee| assignee.identifier())
+                .unwrap_or_default(),
+            status: fields
+                .status
+                .as_ref()
+                .map(|status| status.name.clone())
+                .unwrap_or_default(),
+            status_category: fields
+                .status
+                .as_ref()
+                .and_then(|status| status.category.as_ref())
+                .map(|category| category.key.clone())
+                .unwrap_or_default(),
+            issue_type: name(&fields.issue_type),
+            priority: name(&fields.priority),
+            resolution: name(&fields.resolution),
+            resolution_date: fields.resolution_date.clone(),
+            labels: fields.labels.clone(),
+            sprint_ids: fields.sprints.iter().map(|sprint| sprint.id).collect(),
+        };
+
+        let written = self
+            .connection
+            .call(move |conn| {
+                let tx = conn.transaction().map_err(tokio_rusqlite::Error::Rusqlite)?;
+
+                let latest = tx
+                    .query_row(latest_query, [current.key.as_str()], issue_log_from_row)
+                    .optional()
+                    .map_err(tokio_rusqlite::Error::Rusqlite)?;
+                let last_removed_at = tx
+                    .query_row(last_removed_query, [current.key.as_str()], |row| row.get::<_, Option<String>>(0))
+                    .map_err(tokio_rusqlite::Error::Rusqlite)?;
+                // An issue that shows up again after being removed needs a snapshot after the removal.
+                let changed = !latest.is_some_and(|latest| {
+                    latest.same_values(&current)
+                        && last_removed_at.is_none_or(|removed_at| removed_at < latest.updated_at)
+                });
+
+                if changed {
+                    insert_issue_log(&insert_query, &tx, &current).map_err(tokio_rusqlite::Error::Rusqlite)?;
+                }
+                tx.execute(last_seen_query, (current.key.as_str(), current.updated_at.as_str()))
+                    .map_err(tokio_rusqlite::Error::Rusqlite)?;
+
+                tx.commit().map_err(tokio_rusqlite::Error::Rusqlite)?;
+                Ok(changed)
+            })
+            .await?;
+        Ok(written)
+    }
+
+    /// Inserts historical snapshots, skipping those already stored for the same issue and time.
+    /// Returns the number of inserted snapshots.
+    pub async fn insert_backfilled_issue_logs(&self, logs: Vec<IssueLog>) -> Result<usize, DatabaseError> {
+        let query = format!(
+            "INSERT INTO issues ({})
+            SELECT ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17
+            WHERE NOT EXISTS (SELECT 1 FROM issues WHERE key = ?1 AND updated_at = ?6)",
+            INSERT_ISSUE_LOG_COLUMNS
+        );
+
+        let inserted = self
+            .connection
+            .call(move |conn| {
+                let tx = conn.transaction().map_err(tokio_rusqlite::Error::Rusqlite)?;
+                let mut inserted = 0;
+                for log in &logs {
+                    inserted += insert_issue_log(&query, &tx, log).map_err(tokio_rusqlite::Error::Rusqlite)?;
+                }
+                tx.commit().map_err(tokio_rusqlite::Error::Rusqlite)?;
+                Ok(inserted)
+            })
+            .await?;
+        Ok(inserted)
+    }
+
+    /// Inserts or updates the sprints, e.g. when a sprint was renamed, started or completed.
+    pub async fn upsert_sprints(&self, sprints: Vec<Sprint>) -> Result<(), DatabaseError> {
         let query = "
-            INSERT INTO issues (key, summary, epic_key, time_estimate, time_spent, updated_at, assignee)
+            INSERT INTO sprints (id, name, state, start_date, end_date, complete_date, board_id)
             VALUES (?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(id) DO UPDATE SET
+                name = excluded.name,
+                state = excluded.state,
+                start_date = excluded.start_date,
+                end_date = excluded.end_date,
+                complete_date = excluded.complete_date,
+                board_id = COALESCE(excluded.board_id, board_id)
         ";
-        let key = issue.key.clone();
-        let summary = issue.fields.summary.clone();
-        let epic_key = issue
-            .fields
-            .epic_key
-            .as_ref()
-            .expect("Epic key is missing")
-            .clone();
-        let time_estimate = issue.fields.time_estimate.unwrap_or(0);
-        let time_spent = issue.fields.time_spent.unwrap_or(0);
-        let timestamp = chrono::Utc::now().to_rfc3339();
-        let assignee = issue
-            .fields
-            .assignee
-            .as_ref()
-            .map(|assignee| assignee.name.clone())
-            .unwrap_or_default();
 
         self.connection
             .call(move |conn| {
-                conn.execute(
-                    query,
-                    (
-                        key.as_str(),
-                        summary.as_str(),
-                        epic_key.as_str(),
-                        time_estimate as i64,
-                        time_spent as i64,
-                        timestamp.as_str(),
-                        assignee.as_str(),
-                    ),
-                )
-                .map_err(tokio_rusqlite::Error::Rusqlite)
+                let tx = conn.transaction().map_err(tokio_rusqlite::Error::Rusqlite)?;
+                for sprint in &sprints {
+                    tx.execute(
+                        query,
+                        (
+                            sprint.id,
+                            sprint.name.as_str(),
+                            sprint.state.as_str(),
+                            sprint.start_date.as_deref(),
+                            sprint.end_date.as_deref(),
+                            sprint.complete_date.as_deref(),
+                            sprint.board_id,
+                        ),
+                    )
+                    .map_err(tokio_rusqlite::Error::Rusqlite)?;
+                }
+                tx.commit().map_err(tokio_rusqlite::Error::Rusqlite)
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Returns all known sprints, ordered by start date with unstarted sprints last.
+    pub async fn get_sprints(&self) -> Result<Vec<Sprint>, DatabaseError> {
+        let query = "
+            SELECT * FROM sprints ORDER BY start_date IS NULL, start_date, id
+        ";
+        let sprints = self
+            .connection
+            .call(|conn| {
+                let mut stmt = conn
+                    .prepare_cached(query)
+                    .map_err(tokio_rusqlite::Error::Rusqlite)?;
+
+                let rows = stmt
+                    .query_map([], sprint_from_row)
+                    .map_err(tokio_rusqlite::Error::Rusqlite)?;
+
+                rows.collect::<Result<Vec<_>, _>>()
+                    .map_err(tokio_rusqlite::Error::Rusqlite)
+            })
+            .await?;
+        Ok(sprints)
+    }
+
+    /// Archives every epic that is not in `epic_keys`. Archived epics come back once they are
+    /// inserted again. Returns the number of newly archived epics.
+    pub async fn archive_epics_except(&self, epic_keys: Vec<String>, archived_at: &str) -> Result<usize, DatabaseError> {
+        let active_query = "
+            SELECT key FROM epics WHERE archived_at IS NULL
+        ";
+        let archive_query = "
+            UPDATE epics SET archived_at = ? WHERE key = ?
+        ";
+        let archived_at = archived_at.to_string();
+
+        let archived = self
+            .connection
+            .call(move |conn| {
+                let tx = conn.transaction().map_err(tokio_rusqlite::Error::Rusqlite)?;
+                let active = {
+                    let mut stmt = tx.prepare(active_query).map_err(tokio_rusqlite::Error::Rusqlite)?;
+                    let rows = stmt
+                        .query_map([], |row| row.get::<_, String>(0))
+                        .map_err(tokio_rusqlite::Error::Rusqlite)?;
+                    rows.collect::<Result<Vec<_>, _>>()
+                        .map_err(tokio_rusqlite::Error::Rusqlite)?
+                };
+
+                let mut archived = 0;
+                for key in active.iter().filter(|key| !epic_keys.contains(key)) {
+                    println!("Archiving epic {}: it no longer matches the epics JQL", key);
+                    archived += tx
+                        .execute(archive_query, (archived_at.as_str(), key.as_str()))
+                        .map_err(tokio_rusqlite::Error::Rusqlite)?;
+                }
+
+                tx.commit().map_err(tokio_rusqlite::Error::Rusqlite)?;
+                Ok(archived)
+            })
+            .await?;
+        Ok(archived)
+    }
+
+    pub async fn insert_issue_removals(&self, removals: Vec<IssueRemoval>) -> Result<(), DatabaseError> {
+        let query = "
+            INSERT INTO issue_removals (key, epic_key, removed_at, reason)
+            VALUES (?, ?, ?, ?)
+        ";
+
+        self.connection
+            .call(move |conn| {
+                let tx = conn.transaction().map_err(tokio_rusqlite::Error::Rusqlite)?;
+                for removal in &removals {
+                    tx.execute(
+                        query,
+                        (
+                            removal.key.as_str(),
+                            removal.epic_key.as_str(),
+                            removal.removed_at.as_str(),
+                            removal.reason.as_str(),
+                        ),
+                    )
+                    .map_err(tokio_rusqlite::Error::Rusqlite)?;
+                }
+                tx.commit().map_err(tokio_rusqlite::Error::Rusqlite)
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Returns every recorded removal, most recent first.
+    pub async fn get_issue_removals(&self) -> Result<Vec<IssueRemoval>, DatabaseError> {
+        let query = "
+            SELECT * FROM issue_removals ORDER BY removed_at DESC, key
+        ";
+        let removals = self
+            .connection
+            .call(|conn| {
+                let mut stmt = conn
+                    .prepare_cached(query)
+                    .map_err(tokio_rusqlite::Error::Rusqlite)?;
+
+                let rows = stmt
+                    .query_map([], removal_from_row)
+                    .map_err(tokio_rusqlite::Error::Rusqlite)?;
+
+                rows.collect::<Result<Vec<_>, _>>()
+                    .map_err(tokio_rusqlite::Error::Rusqlite)
+            })
+            .await?;
+        Ok(removals)
+    }
+
+    /// Stores a new sync run and returns its ID.
+    pub async fn insert_sync_run(&self, run: &SyncRun) -> Result<i64, DatabaseError> {
+        let query = "
+            INSERT INTO sync_runs (
+                started_at, finished_at, epic_key, full_sync, epics_fetched, issues_fetched,
+                snapshots_written, issues_removed, error
+            )
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+        ";
+        let params = (
+            run.started_at.clone(),
+            run.finished_at.clone(),
+            run.epic_key.clone(),
+            run.full_sync,
+            run.epics_fetched,
+            run.issues_fetched,
+            run.snapshots_written,
+            run.issues_removed,
+            run.error.clone(),
+        );
+
+        let id = self
+            .connection
+            .call(move |conn| {
+                conn.execute(query, params)
+                    .map_err(tokio_rusqlite::Error::Rusqlite)?;
+                Ok(conn.last_insert_rowid())
+            })
+            .await?;
+        Ok(id)
+    }
+
+    pub async fn update_sync_run(&self, run: &SyncRun) -> Result<(), DatabaseError> {
+        let query = "
+            UPDATE sync_runs
+            SET finished_at = ?, full_sync = ?, epics_fetched = ?, issues_fetched = ?,
+                snapshots_written = ?, issues_removed = ?, error = ?
+            WHERE id = ?
+        ";
+        let params = (
+            run.finished_at.clone(),
+            run.full_sync,
+            run.epics_fetched,
+            run.issues_fetched,
+            run.snapshots_written,
+            run.issues_removed,
+            run.error.clone(),
+            run.id,
+        );
+
+        self.connection
+            .call(move |conn| {
+                conn.execute(query, params)
+                    .map_err(tokio_rusqlite::Error::Rusqlite)
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Returns the most recent sync runs first, all of them when `limit` is `None`.
+    pub async fn get_sync_runs(&self, limit: Option<usize>) -> Result<Vec<SyncRun>, DatabaseError> {
+        let query = "
+            SELECT * FROM sync_runs ORDER BY started_at DESC, id DESC LIMIT ?
+        ";
+        // A negative limit means no limit to SQLite.
+        let limit = limit.map(|limit| limit as i64).unwrap_or(-1);
+
+        let runs = self
+            .connection
+            .call(move |conn| {
+                let mut stmt = conn
+                    .prepare_cached(query)
+                    .map_err(tokio_rusqlite::Error::Rusqlite)?;
+
+                let rows = stmt
+                    .query_map([limit], sync_run_from_row)
+                    .map_err(tokio_rusqlite::Error::Rusqlite)?;
+
+                rows.collect::<Result<Vec<_>, _>>()
+                    .map_err(tokio_rusqlite::Error::Rusqlite)
+            })
+            .await?;
+        Ok(runs)
+    }
+
+    /// Returns the most recent sync of every epic that finished without an error.
+    pub async fn get_last_successful_sync_run(&self) -> Result<Option<SyncRun>, DatabaseError> {
+        let query = "
+            SELECT *
+            FROM sync_runs
+            WHERE finished_at IS NOT NULL AND error IS NULL AND epic_key IS NULL
+            ORDER BY started_at DESC, id DESC
+            LIMIT 1
+        ";
+
+        let run = self
+            .connection
+            .call(|conn| {
+                conn.query_row(query, [], sync_run_from_row)
+                    .optional()
+                    .map_err(tokio_rusqlite::Error::Rusqlite)
+            })
+            .await?;
+        Ok(run)
+    }
+
+    pub async fn get_issue_last_seen(&self, issue_key: &str) -> Result<Option<String>, DatabaseError> {
+        let query = "
+            SELECT last_seen_at FROM issue_last_seen WHERE key = ?
+        ";
+        let issue_key = issue_key.to_string();
+        let last_seen = self
+            .connection
+            .call(move |conn| {
+                let mut stmt = conn
+                    .prepare_cached(query)
+                    .map_err(tokio_rusqlite::Error::Rusqlite)?;
+
+                stmt.query_row([issue_key.as_str()], |row| row.get::<_, String>(0))
+                    .optional()
+                    .map_err(tokio_rusqlite::Error::Rusqlite)
+            })
+            .await?;
+        Ok(last_seen)
+    }
+
+    /// Marks every issue that was seen since `seen_since` as seen again at `seen_at`.
+    pub async fn touch_issues_seen_since(&self, seen_since: &str, seen_at: &str) -> Result<(), DatabaseError> {
+        let query = "
+            UPDATE issue_last_seen
+            SET last_seen_at = ?
+            WHERE last_seen_at >= ? AND last_seen_at < ?
+        ";
+        let seen_since = seen_since.to_string();
+        let seen_at = seen_at.to_string();
+
+        self.connection
+            .call(move |conn| {
+                conn.execute(query, (seen_at.as_str(), seen_since.as_str(), seen_at.as_str()))
+                    .map_err(tokio_rusqlite::Error::Rusqlite)
             })
             .await?;
         Ok(())
     }
 
+    /// Collapses runs of identical consecutive snapshots written before change detection existed,
+    /// keeping the first snapshot of each run. Returns the number of removed rows.
+    pub async fn compact_issue_snapshots(&self) -> Result<usize, DatabaseError> {
+        let last_seen_query = "
+            INSERT INTO issue_last_seen (key, last_seen_at)
+            SELECT key, MAX(updated_at) FROM issues WHERE true GROUP BY key
+            ON CONFLICT(key) DO UPDATE SET
+                last_seen_at = MAX(last_seen_at, excluded.last_seen_at)
+        ";
+        let delete_query = "
+            DELETE FROM issues
+            WHERE rowid IN (
+                SELECT rowid
+                FROM (
+                    SELECT rowid, summary, epic_key, time_estimate, time_spent, assignee,
+                        status, status_category, issue_type, priority, resolution, resolution_date, labels,
+                        remaining_estimate, story_points, sprint_ids,
+                        LAG(summary) OVER snapshots AS previous_summary,
+                        LAG(epic_key) OVER snapshots AS previous_epic_key,
+                        LAG(time_estimate) OVER snapshots AS previous_time_estimate,
+                        LAG(time_spent) OVER snapshots AS previous_time_spent,
+                        LAG(assignee) OVER snapshots AS previous_assignee,
+                        LAG(status) OVER snapshots AS previous_status,
+                        LAG(status_category) OVER snapshots AS previous_status_category,
+                        LAG(issue_type) OVER snapshots AS previous_issue_type,
+                        LAG(priority) OVER snapshots AS previous_priority,
+                        LAG(resolution) OVER snapshots AS previous_resolution,
+                        LAG(resolution_date) OVER snapshots AS previous_resolution_date,
+                        LAG(labels) OVER snapshots AS previous_labels,
+                        LAG(remaining_estimate) OVER snapshots AS previous_remaining_estimate,
+                        LAG(story_points) OVER snapshots AS previous_story_points,
+                        LAG(sprint_ids) OVER snapshots AS previous_sprint_ids
+                    FROM issues
+                    WINDOW snapshots AS (PARTITION BY key ORDER BY updated_at)
+                ) ordered
+                WHERE previous_summary IS NOT NULL
+                    AND summary = previous_summary
+                    AND epic_key = previous_epic_key
+                    AND time_estimate = previous_time_estimate
+                    AND time_spent = previous_time_spent
+                    AND assignee = previous_assignee
+                    AND status = previous_status
+                    AND status_category = previous_status_category
+                    AND issue_type = previous_issue_type
+                    AND priority = previous_priority
+                    AND resolution = previous_resolution
+                    AND resolution_date IS previous_resolution_date
+                    AND labels = previous_labels
+                    AND remaining_estimate = previous_remaining_estimate
+                    AND story_points IS previous_story_points
+                    AND sprint_ids = previous_sprint_ids
+            )
+        ";
+
+        let removed = self
+            .connection
+            .call(move |conn| {
+                let tx = conn.transaction().map_err(tokio_rusqlite::Error::Rusqlite)?;
+                tx.execute(last_seen_query, [])
+                    .map_err(tokio_rusqlite::Error::Rusqlite)?;
+                let removed = tx
+                    .execute(delete_query, [])
+                    .map_err(tokio_rusqlite::Error::Rusqlite)?;
+                tx.commit().map_err(tokio_rusqlite::Error::Rusqlite)?;
+                Ok(removed)
+            })
+            .await?;
+        Ok(removed)
+    }
+
     pub async fn get_logs_of_issue(&self, issue_key: &str) -> Result<Vec<IssueLog>, DatabaseError> {
         let query = "
             SELECT * FROM issues WHERE key = ?
@@ -156,23 +742,117 @@ impl Database {
             .call(move |conn| {
                 let mut stmt = conn
                     .prepare_cached(query)
-                    .map_err(|e| tokio_rusqlite::Error::Rusqlite(e))?;
-
-                let rows = stmt.query_map([issue_key.as_str()], |row| {
-                    Ok(IssueLog {
-                        key: row.get("key")?,
-                        summary: row.get("summary")?,
-                        epic_key: row.get("epic_key")?,
-                        time_estimate: row.get("time_estimate")?,
-                        time_spent: row.get("time_spent")?,
-                        updated_at: row.get("updated_at")?,
-                        assignee: row.get("assignee")?,
-                    })
+                    .map_err(tokio_rusqlite::Error::Rusqlite)?;
+
+                let rows = stmt
+                    .query_map([issue_key.as_str()], issue_log_from_row)
+                    .map_err(tokio_rusqlite::Error::Rusqlite)?;
+
+                rows.collect::<Result<Vec<_>, _>>()
+                .map_err(tokio_rusqlite::Error::Rusqlite)
+            })
+            .await?;
+        Ok(logs)
+    }
+
+    /// Returns every snapshot of the issues that have been part of the epic at some point,
+    /// including snapshots from after they left it. A removed issue gets an extra snapshot of its
+    /// last values without an epic at the time of removal, so it stops counting from then on.
+    pub async fn get_logs_of_epic(&self, epic_key: &str) -> Result<Vec<IssueLog>, DatabaseError> {
+        let query = "
+            SELECT *
+            FROM issues
+            WHERE key IN (SELECT DISTINCT key FROM issues WHERE epic_key = ?)
+            ORDER BY updated_at
+        ";
+        let removals_query = "
+            SELECT *
+            FROM issue_removals
+            WHERE key IN (SELECT DISTINCT key FROM issues WHERE epic_key = ?)
+        ";
+        let epic_key = epic_key.to_string();
+        let (mut logs, removals) = self
+            .connection
+            .call(move |conn| {
+                let mut stmt = conn
+                    .prepare_cached(query)
+                    .map_err(tokio_rusqlite::Error::Rusqlite)?;
+                let logs = stmt
+                    .query_map([epic_key.as_str()], issue_log_from_row)
+                    .map_err(tokio_rusqlite::Error::Rusqlite)?
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(tokio_rusqlite::Error::Rusqlite)?;
+
+                let mut stmt = conn
+                    .prepare_cached(removals_query)
+                    .map_err(tokio_rusqlite::Error::Rusqlite)?;
+                let removals = stmt
+                    .query_map([epic_key.as_str()], removal_from_row)
+                    .map_err(tokio_rusqlite::Error::Rusqlite)?
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(tokio_rusqlite::Error::Rusqlite)?;
+
+                Ok((logs, removals))
+            })
+            .await?;
+
+        let removed_logs = removals
+            .iter()
+            .filter_map(|removal| {
+                let removed = logs
+                    .iter()
+                    .rfind(|log| log.key == removal.key && log.updated_at <= removal.removed_at)?;
+                Some(IssueLog {
+                    epic_key: String::new(),
+                    updated_at: removal.removed_at.clone(),
+                    ..removed.clone()
                 })
-                .map_err(|e| tokio_rusqlite::Error::Rusqlite(e))?;
+            })
+            .collect::<Vec<_>>();
+        logs.extend(removed_logs);
+        logs.sort_by(|a, b| a.updated_at.cmp(&b.updated_at));
+        Ok(logs)
+    }
+
+    pub async fn get_epic_last_seen(&self, epic_key: &str) -> Result<Option<String>, DatabaseError> {
+        let query = "
+            SELECT MAX(last_seen_at)
+            FROM issue_last_seen
+            WHERE key IN (SELECT DISTINCT key FROM issues WHERE epic_key = ?)
+        ";
+        let epic_key = epic_key.to_string();
+        let last_seen = self
+            .connection
+            .call(move |conn| {
+                let mut stmt = conn
+                    .prepare_cached(query)
+                    .map_err(tokio_rusqlite::Error::Rusqlite)?;
+
+                stmt.query_row([epic_key.as_str()], |row| row.get::<_, Option<String>>(0))
+                    .map_err(tokio_rusqlite::Error::Rusqlite)
+            })
+            .await?;
+        Ok(last_seen)
+    }
+
+    /// Returns every stored snapshot, ordered by issue and time.
+    pub async fn get_all_issue_logs(&self) -> Result<Vec<IssueLog>, DatabaseError> {
+        let query = "
+            SELECT * FROM issues ORDER BY key, updated_at
+        ";
+        let logs = self
+            .connection
+            .call(|conn| {
+                let mut stmt = conn
+                    .prepare_cached(query)
+                    .map_err(tokio_rusqlite::Error::Rusqlite)?;
+
+                let rows = stmt
+                    .query_map([], issue_log_from_row)
+                    .map_err(tokio_rusqlite::Error::Rusqlite)?;
 
                 rows.collect::<Result<Vec<_>, _>>()
-                .map_err(|e| tokio_rusqlite::Error::Rusqlite(e))
+                    .map_err(tokio_rusqlite::Error::Rusqlite)
             })
             .await?;
         Ok(logs)
@@ -187,6 +867,10 @@ impl Database {
                 FROM issues
             ) ranked
             WHERE rn = 1
+                AND NOT EXISTS (
+                    SELECT 1 FROM issue_removals
+                    WHERE issue_removals.key = ranked.key AND issue_removals.removed_at >= ranked.updated_at
+                )
         ";
         
         let logs = self
@@ -194,36 +878,52 @@ impl Database {
             .call(|conn| {
                 let mut stmt = conn
                     .prepare_cached(query)
-                    .map_err(|e| tokio_rusqlite::Error::Rusqlite(e))?;
+                    .map_err(tokio_rusqlite::Error::Rusqlite)?;
 
                 let rows = stmt
-                    .query_map([], |row| {
-                        Ok(IssueLog {
-                            key: row.get("key")?,
-                            summary: row.get("summary")?,
-                            epic_key: row.get("epic_key")?,
-                            time_estimate: row.get("time_estimate")?,
-                            time_spent: row.get("time_spent")?,
-                            updated_at: row.get("updated_at")?,
-                            assignee: row.get("assignee")?,
-                        })
-                    })
-                    .map_err(|e| tokio_rusqlite::Error::Rusqlite(e))?;
+                    .query_map([], issue_log_from_row)
+                    .map_err(tokio_rusqlite::Error::Rusqlite)?;
 
                 rows.collect::<Result<Vec<_>, _>>()
-                    .map_err(|e| tokio_rusqlite::Error::Rusqlite(e))
+                    .map_err(tokio_rusqlite::Error::Rusqlite)
             })
             .await?;
 
         Ok(logs)
     }
 
+    /// Returns the latest snapshot of the issue, `None` if it was never stored or has been removed since.
+    pub async fn get_latest_issue_log(&self, issue_key: &str) -> Result<Option<IssueLog>, DatabaseError> {
+        let query = "
+            SELECT *
+            FROM issues
+            WHERE key = ?
+                AND NOT EXISTS (
+                    SELECT 1 FROM issue_removals
+                    WHERE issue_removals.key = issues.key AND issue_removals.removed_at >= issues.updated_at
+                )
+            ORDER BY updated_at DESC
+            LIMIT 1
+        ";
+        let issue_key = issue_key.to_string();
+        let log = self
+            .connection
+            .call(move |conn| {
+                conn.query_row(query, [issue_key.as_str()], issue_log_from_row)
+                    .optional()
+                    .map_err(tokio_rusqlite::Error::Rusqlite)
+            })
+            .await?;
+        Ok(log)
+    }
+
     pub async fn get_epics(&self) -> Result<Vec<Epic>, DatabaseError> {
         const QUERY: &str = r#"
             SELECT 
                 key,
                 summary 
             FROM epics
+            WHERE archived_at IS NULL
             ORDER BY key
         "#;
 
@@ -232,7 +932,7 @@ impl Database {
             .call(|conn| {
                 let mut stmt = conn
                     .prepare_cached(QUERY)
-                    .map_err(|e| tokio_rusqlite::Error::Rusqlite(e))?;
+                    .map_err(tokio_rusqlite::Error::Rusqlite)?;
 
                 let rows = stmt
                     .query_map([], |row| {
@@ -241,10 +941,10 @@ impl Database {
                             summary: row.get("summary")?,
                         })
                     })
-                    .map_err(|e| tokio_rusqlite::Error::Rusqlite(e))?;
+                    .map_err(tokio_rusqlite::Error::Rusqlite)?;
 
                 rows.collect::<Result<Vec<_>, _>>()
-                    .map_err(|e| tokio_rusqlite::Error::Rusqlite(e))
+                    .map_err(tokio_rusqlite::Error::Rusqlite)
             })
             .await?;
 
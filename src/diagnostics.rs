@@ -0,0 +1,121 @@
+use crate::config::Config;
+use crate::database::{Database, DatabaseError};
+use crate::jira_client::{EpicRelation, JiraClient};
+use crate::migrations;
+use serde::Serialize;
+
+#[derive(Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub ok: bool,
+    pub detail: String,
+}
+
+impl Check {
+    fn new<E: std::fmt::Display>(name: &'static str, result: Result<String, E>) -> Self {
+        match result {
+            Ok(detail) => Self { name, ok: true, detail },
+            Err(e) => Self {
+                name,
+                ok: false,
+                detail: e.to_string(),
+            },
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Diagnostics {
+    pub ok: bool,
+    pub checks: Vec<Check>,
+}
+
+impl Diagnostics {
+    pub fn to_text(&self) -> String {
+        self.checks
+            .iter()
+            .map(|check| {
+                format!(
+                    "[{}] {}: {}\n",
+                    if check.ok { " ok " } else { "FAIL" },
+                    check.name,
+                    check.detail
+                )
+            })
+            .collect()
+    }
+}
+
+async fn check_jql(jira_client: &JiraClient, jql: &str) -> Result<String, String> {
+    match jira_client.count_jql(jql).await {
+        Ok(0) => Err(format!("{:?} returned no issues", jql)),
+        Ok(count) => Ok(format!("{:?} returned {} issues", jql, count)),
+        Err(e) => Err(format!("{:?} failed: {}", jql, e)),
+    }
+}
+
+async fn check_epic_link_field(jira_client: &JiraClient) -> Result<String, String> {
+    let field_id = match jira_client.epic_relation().await {
+        Ok(EpicRelation::Parent) => return Ok("parent hierarchy".to_string()),
+        Ok(EpicRelation::Field(field_id)) => field_id,
+        Err(e) => return Err(format!("{}, check JIRA_EPIC_LINK_FIELD", e)),
+    };
+    let fields = jira_client.get_fields().await.map_err(|e| e.to_string())?;
+    fields
+        .iter()
+        .find(|field| &field.id == field_id)
+        .map(|field| format!("{} ({})", field.id, field.name))
+        .ok_or_else(|| format!("Jira has no field {}, check JIRA_EPIC_LINK_FIELD", field_id))
+}
+
+async fn check_schema(database: &Database) -> Result<String, DatabaseError> {
+    let version = database.get_schema_version().await?;
+    let latest = migrations::latest_version();
+    if version > latest {
+        return Err(DatabaseError::SchemaTooNew(version, latest));
+    }
+    if version < latest {
+        return Ok(format!(
+            "version {}, {} migration(s) pending, they are applied on the next start",
+            version,
+            latest - version
+        ));
+    }
+    Ok(format!("version {}", version))
+}
+
+/// Checks the Jira credentials, queries and fields as well as the database, so misconfiguration
+/// shows up before the first collection instead of during it.
+pub async fn run_diagnostics(jira_client: &JiraClient, database: &Database, config: &Config) -> Diagnostics {
+    let checks = vec![
+        Check::new(
+            "Jira authentication",
+            jira_client
+                .get_myself()
+                .await
+                .map(|user| match &user.email {
+                    Some(email) => format!("Authenticated as {} ({})", user.identifier(), email),
+                    None => format!("Authenticated as {}", user.identifier()),
+                }),
+        ),
+        Check::new(
+            "Epics JQL",
+            check_jql(jira_client, &format!("{} AND type = Epic", config.jira.epics_jql)).await,
+        ),
+        Check::new("Issues JQL", check_jql(jira_client, &config.jira.issues_jql).await),
+        Check::new("Epic link field", check_epic_link_field(jira_client).await),
+        Check::new(
+            "Database writable",
+            database
+                .check_writable()
+                .await
+                .map(|_| config.database.path.clone()),
+        ),
+        Check::new("Database schema", check_schema(database).await),
+    ];
+
+    Diagnostics {
+        ok: checks.iter().all(|check| check.ok),
+        checks,
+    }
+}
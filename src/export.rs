@@ -0,0 +1,57 @@
+use crate::models::{Epic, IssueLog};
+use itertools::Itertools;
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct Export<'a> {
+    epics: &'a [Epic],
+    issues: &'a [IssueLog],
+}
+
+pub fn to_json(epics: &[Epic], issue_logs: &[IssueLog]) -> Result<String, serde_json::Error> {
+    serde_json::to_string_pretty(&Export {
+        epics,
+        issues: issue_logs,
+    })
+}
+
+fn escape_csv(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// One row per issue snapshot; the epic summaries are not part of the CSV export.
+pub fn to_csv(issue_logs: &[IssueLog]) -> String {
+    let mut csv = String::from(
+        "key,summary,epic_key,time_estimate,time_spent,updated_at,assignee,\
+        status,status_category,issue_type,priority,resolution,resolution_date,labels,\
+        remaining_estimate,story_points,sprint_ids\n",
+    );
+    for log in issue_logs {
+        // Jira labels cannot contain spaces, so they are joined by one, like the sprint IDs.
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}\n",
+            escape_csv(&log.key),
+            escape_csv(&log.summary),
+            escape_csv(&log.epic_key),
+            log.time_estimate,
+            log.time_spent,
+            escape_csv(&log.updated_at),
+            escape_csv(&log.assignee),
+            escape_csv(&log.status),
+            escape_csv(&log.status_category),
+            escape_csv(&log.issue_type),
+            escape_csv(&log.priority),
+            escape_csv(&log.resolution),
+            escape_csv(log.resolution_date.as_deref().unwrap_or_default()),
+            escape_csv(&log.labels.join(" ")),
+            log.remaining_estimate,
+            log.story_points.map(|points| points.to_string()).unwrap_or_default(),
+            log.sprint_ids.iter().join(" "),
+        ));
+    }
+    csv
+}
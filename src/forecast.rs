@@ -0,0 +1,144 @@
+use crate::metrics::{epic_metric_totals_over_time, Metric, SECONDS_PER_DAY};
+use crate::models::IssueLog;
+use serde::Serialize;
+
+/// z-score of a two-sided 80% confidence interval, used for the range of the daily burn.
+const CONFIDENCE_Z: f64 = 1.2816;
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ForecastStatus {
+    /// Nothing is left of the remaining estimate.
+    Done,
+    /// The remaining estimate goes down, so a completion date could be projected.
+    Projected,
+    /// The remaining estimate did not go down over the window, e.g. because it grew as fast as work was done.
+    NoProgress,
+    /// Less than a day of snapshots to measure a burn rate from.
+    InsufficientHistory,
+}
+
+#[derive(Serialize)]
+pub struct Forecast {
+    pub epic_key: String,
+    pub status: ForecastStatus,
+    /// Days the burn rates were measured over, at most the configured window.
+    pub window_days: i64,
+    pub remaining_estimate: i64,
+    /// Seconds of time logged per day over the window.
+    pub time_spent_per_day: f64,
+    /// Seconds the remaining estimate went down per day over the window, net of re-estimates.
+    pub remaining_burn_per_day: f64,
+    pub expected_completion: Option<String>,
+    pub earliest_completion: Option<String>,
+    /// `None` when the slow end of the range makes no progress, so there is no latest date.
+    pub latest_completion: Option<String>,
+}
+
+impl Forecast {
+    /// The expected completion date as Unix timestamp, for plotting.
+    pub fn expected_completion_timestamp(&self) -> Option<i64> {
+        let completion = self.expected_completion.as_deref()?;
+        Some(
+            chrono::DateTime::parse_from_rfc3339(completion)
+                .ok()?
+                .timestamp(),
+        )
+    }
+}
+
+fn mean_and_standard_deviation(values: &[f64]) -> (f64, f64) {
+    let count = values.len() as f64;
+    let mean = values.iter().fold(0.0, |sum, value| sum + value) / count;
+    let variance = values
+        .iter()
+        .fold(0.0, |sum, value| sum + (value - mean).powi(2))
+        / (count - 1.0).max(1.0);
+    (mean, variance.sqrt())
+}
+
+/// The moment `remaining` seconds of work are done when burning `rate` seconds per day.
+fn completion_at(now: chrono::DateTime<chrono::Utc>, remaining: f64, rate: f64) -> Option<String> {
+    if rate <= 0.0 {
+        return None;
+    }
+    let seconds = (remaining / rate * SECONDS_PER_DAY as f64).ceil() as i64;
+    Some((now + chrono::Duration::seconds(seconds)).to_rfc3339())
+}
+
+/// Projects when the remaining estimate of the epic reaches zero from how fast it went down per
+/// day over the last `window_days`. The range is the 80% confidence interval of the mean daily burn.
+pub fn forecast_epic(
+    epic_key: &str,
+    issue_logs: &[IssueLog],
+    window_days: i64,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<Forecast, chrono::ParseError> {
+    let totals = epic_metric_totals_over_time(
+        epic_key,
+        issue_logs,
+        &[Metric::TimeSpent, Metric::RemainingEstimate],
+    )?;
+    let remaining = totals.last().map(|totals| totals.values[1]).unwrap_or(0.0);
+
+    // Totals at the end of each day of the window, counting back from now.
+    let days = totals
+        .first()
+        .map(|first| ((now.timestamp() - first.date) / SECONDS_PER_DAY).min(window_days))
+        .unwrap_or(0)
+        .max(0);
+    let samples = (0..=days)
+        .rev()
+        .map(|days_ago| {
+            let date = now.timestamp() - days_ago * SECONDS_PER_DAY;
+            totals
+                .iter()
+                .take_while(|totals| totals.date <= date)
+                .last()
+                .map(|totals| (totals.values[0], totals.values[1]))
+                .unwrap_or((0.0, 0.0))
+        })
+        .collect::<Vec<_>>();
+    let spent_per_day = samples
+        .windows(2)
+        .map(|pair| pair[1].0 - pair[0].0)
+        .collect::<Vec<_>>();
+    let burn_per_day = samples
+        .windows(2)
+        .map(|pair| pair[0].1 - pair[1].1)
+        .collect::<Vec<_>>();
+
+    let mut forecast = Forecast {
+        epic_key: epic_key.to_string(),
+        status: ForecastStatus::InsufficientHistory,
+        window_days: days,
+        remaining_estimate: remaining as i64,
+        time_spent_per_day: 0.0,
+        remaining_burn_per_day: 0.0,
+        expected_completion: None,
+        earliest_completion: None,
+        latest_completion: None,
+    };
+    if !totals.is_empty() && remaining <= 0.0 {
+        forecast.status = ForecastStatus::Done;
+        return Ok(forecast);
+    }
+    if burn_per_day.is_empty() {
+        return Ok(forecast);
+    }
+
+    let (burn, burn_deviation) = mean_and_standard_deviation(&burn_per_day);
+    forecast.time_spent_per_day = mean_and_standard_deviation(&spent_per_day).0;
+    forecast.remaining_burn_per_day = burn;
+    if burn <= 0.0 {
+        forecast.status = ForecastStatus::NoProgress;
+        return Ok(forecast);
+    }
+
+    let margin = CONFIDENCE_Z * burn_deviation / (burn_per_day.len() as f64).sqrt();
+    forecast.status = ForecastStatus::Projected;
+    forecast.expected_completion = completion_at(now, remaining, burn);
+    forecast.earliest_completion = completion_at(now, remaining, burn + margin);
+    forecast.latest_completion = completion_at(now, remaining, burn - margin);
+    Ok(forecast)
+}
@@ -0,0 +1,65 @@
+use crate::config::GraphSize;
+use crate::monte_carlo::MonteCarloForecast;
+use crate::renderer::{Bar, Marker, Renderer};
+
+const PERCENTILE_COLORS: [(u32, &str); 3] = [(50, "#27AE60"), (85, "#F39C12"), (95, "#E74C3C")];
+
+/// Renders how many simulated runs finished after each number of days from now, with the
+/// P50, P85 and P95 completion dates marked. Empty unless the forecast could be simulated.
+pub fn render_forecast_histogram(
+    forecast: &MonteCarloForecast,
+    size: &GraphSize,
+) -> Result<axum::response::Html<String>, chrono::ParseError> {
+    let mut renderer = Renderer::new(size.width, size.height, size.margin);
+
+    let max_days = forecast
+        .histogram
+        .iter()
+        .map(|bin| bin.days)
+        .max()
+        .unwrap_or(0);
+    let max_runs = forecast
+        .histogram
+        .iter()
+        .map(|bin| bin.runs)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    let bar_width = renderer.width as f64 / (max_days + 1) as f64;
+    let height = renderer.height as f64;
+
+    let bars = forecast
+        .histogram
+        .iter()
+        .map(|bin| Bar {
+            x: bin.days as f64 * bar_width,
+            y: height - bin.runs as f64 / max_runs as f64 * height,
+            width: bar_width,
+        })
+        .collect();
+
+    let mut markers = Vec::new();
+    for percentile in &forecast.percentiles {
+        let (Some(days), Some(completion)) = (percentile.days, percentile.completion.as_deref())
+        else {
+            continue;
+        };
+        let color = PERCENTILE_COLORS
+            .iter()
+            .find(|(value, _)| *value == percentile.percentile)
+            .map_or("#4A90E2", |(_, color)| color);
+        markers.push(Marker {
+            x: (days as f64 + 0.5) * bar_width,
+            label: format!(
+                "P{} {}",
+                percentile.percentile,
+                chrono::DateTime::parse_from_rfc3339(completion)?.format("%Y-%m-%d")
+            ),
+            color,
+        });
+    }
+
+    Ok(axum::response::Html(
+        renderer.render_histogram(bars, "#4A90E2", markers),
+    ))
+}
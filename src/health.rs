@@ -0,0 +1,222 @@
+use crate::metrics::{epic_totals_over_time, EpicMetrics, SECONDS_PER_DAY};
+use crate::models::IssueLog;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Green,
+    Amber,
+    Red,
+}
+
+impl HealthStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HealthStatus::Green => "green",
+            HealthStatus::Amber => "amber",
+            HealthStatus::Red => "red",
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct HealthFinding {
+    pub rule: &'static str,
+    pub status: HealthStatus,
+    pub reason: String,
+}
+
+#[derive(Serialize)]
+pub struct EpicHealth {
+    pub epic_key: String,
+    pub status: HealthStatus,
+    pub findings: Vec<HealthFinding>,
+}
+
+/// Thresholds of the health rules. A rule turns amber once its amber threshold is reached and red once
+/// its red threshold is reached.
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct HealthRules {
+    pub overrun_amber_percent: f64,
+    pub overrun_red_percent: f64,
+    pub unestimated_amber_count: usize,
+    pub unestimated_red_percent: f64,
+    pub stale_amber_days: i64,
+    pub stale_red_days: i64,
+    pub estimate_growth_amber_percent: f64,
+    pub estimate_growth_red_percent: f64,
+    /// Leave issues in the done status category out of the overrun and unestimated rules, and
+    /// don't report epics whose issues are all done as stale.
+    pub ignore_done_issues: bool,
+}
+
+impl Default for HealthRules {
+    fn default() -> Self {
+        Self {
+            overrun_amber_percent: 0.0,
+            overrun_red_percent: 20.0,
+            unestimated_amber_count: 1,
+            unestimated_red_percent: 50.0,
+            stale_amber_days: 7,
+            stale_red_days: 14,
+            estimate_growth_amber_percent: 20.0,
+            estimate_growth_red_percent: 50.0,
+            ignore_done_issues: true,
+        }
+    }
+}
+
+fn grade<T: PartialOrd>(value: T, amber: T, red: T) -> HealthStatus {
+    if value >= red {
+        HealthStatus::Red
+    } else if value >= amber {
+        HealthStatus::Amber
+    } else {
+        HealthStatus::Green
+    }
+}
+
+/// Returns the latest snapshot of every issue that is currently part of the epic.
+pub fn latest_logs_of_epic<'a>(epic_key: &str, issue_logs: &'a [IssueLog]) -> Vec<&'a IssueLog> {
+    let mut latest_logs: HashMap<&str, &IssueLog> = HashMap::new();
+    for log in issue_logs {
+        match latest_logs.get(log.key.as_str()) {
+            Some(latest) if latest.updated_at >= log.updated_at => {}
+            _ => {
+                latest_logs.insert(&log.key, log);
+            }
+        }
+    }
+    latest_logs
+        .into_values()
+        .filter(|log| log.epic_key == epic_key)
+        .collect()
+}
+
+fn check_overrun(metrics: &EpicMetrics, rules: &HealthRules) -> Option<HealthFinding> {
+    if metrics.time_estimate == 0 || metrics.time_spent <= metrics.time_estimate {
+        return None;
+    }
+    let overrun = (metrics.time_spent - metrics.time_estimate) as f64 / metrics.time_estimate as f64 * 100.0;
+    Some(HealthFinding {
+        rule: "overrun",
+        status: grade(overrun, rules.overrun_amber_percent, rules.overrun_red_percent),
+        reason: format!("Time spent exceeds the estimate by {:.0}%", overrun),
+    })
+}
+
+fn check_unestimated(metrics: &EpicMetrics, rules: &HealthRules) -> Option<HealthFinding> {
+    if metrics.unestimated_issue_count == 0 {
+        return None;
+    }
+    let percent = metrics.unestimated_issue_count as f64 / metrics.issue_count as f64 * 100.0;
+    let status = if percent >= rules.unestimated_red_percent {
+        HealthStatus::Red
+    } else if metrics.unestimated_issue_count >= rules.unestimated_amber_count {
+        HealthStatus::Amber
+    } else {
+        HealthStatus::Green
+    };
+    Some(HealthFinding {
+        rule: "unestimated",
+        status,
+        reason: format!(
+            "{} of {} issues have no estimate",
+            metrics.unestimated_issue_count, metrics.issue_count
+        ),
+    })
+}
+
+fn check_stale(
+    epic_key: &str,
+    issue_logs: &[IssueLog],
+    rules: &HealthRules,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<Option<HealthFinding>, chrono::ParseError> {
+    let totals = epic_totals_over_time(epic_key, issue_logs)?;
+    let Some(first) = totals.first() else {
+        return Ok(None);
+    };
+    let last_progress = totals
+        .windows(2)
+        .rfind(|pair| pair[1].time_spent > pair[0].time_spent)
+        .map(|pair| pair[1].date)
+        .unwrap_or(first.date);
+
+    let days = (now.timestamp() - last_progress) / SECONDS_PER_DAY;
+    Ok(Some(HealthFinding {
+        rule: "stale",
+        status: grade(days, rules.stale_amber_days, rules.stale_red_days),
+        reason: format!("No progress logged in {} days", days),
+    }))
+}
+
+fn check_estimate_growth(
+    epic_key: &str,
+    issue_logs: &[IssueLog],
+    rules: &HealthRules,
+) -> Result<Option<HealthFinding>, chrono::ParseError> {
+    let totals = epic_totals_over_time(epic_key, issue_logs)?;
+    let (Some(initial), Some(current)) = (totals.iter().find(|totals| totals.time_estimate > 0), totals.last()) else {
+        return Ok(None);
+    };
+    if current.time_estimate <= initial.time_estimate {
+        return Ok(None);
+    }
+
+    let growth = (current.time_estimate - initial.time_estimate) as f64 / initial.time_estimate as f64 * 100.0;
+    Ok(Some(HealthFinding {
+        rule: "estimate_growth",
+        status: grade(
+            growth,
+            rules.estimate_growth_amber_percent,
+            rules.estimate_growth_red_percent,
+        ),
+        reason: format!("Estimate grew by {:.0}% since start", growth),
+    }))
+}
+
+/// Evaluates the epic against all health rules, using every snapshot of the issues that have been part of it.
+pub fn evaluate_epic(
+    epic_key: &str,
+    issue_logs: &[IssueLog],
+    rules: &HealthRules,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<EpicHealth, chrono::ParseError> {
+    let latest_logs = latest_logs_of_epic(epic_key, issue_logs);
+    let open_logs = latest_logs
+        .iter()
+        .copied()
+        .filter(|log| !(rules.ignore_done_issues && log.is_done()))
+        .collect::<Vec<_>>();
+    let metrics = EpicMetrics::from_latest_logs(epic_key, &open_logs);
+    let finished = !latest_logs.is_empty() && open_logs.is_empty();
+
+    let findings = [
+        check_overrun(&metrics, rules),
+        check_unestimated(&metrics, rules),
+        if finished {
+            None
+        } else {
+            check_stale(epic_key, issue_logs, rules, now)?
+        },
+        check_estimate_growth(epic_key, issue_logs, rules)?,
+    ]
+    .into_iter()
+    .flatten()
+    .filter(|finding| finding.status != HealthStatus::Green)
+    .collect::<Vec<_>>();
+
+    Ok(EpicHealth {
+        epic_key: epic_key.to_string(),
+        status: findings
+            .iter()
+            .map(|finding| finding.status)
+            .max()
+            .unwrap_or(HealthStatus::Green),
+        findings,
+    })
+}
@@ -1,71 +1,706 @@
+use crate::cassette::{Cassette, CassetteError, Interaction};
+use crate::config::{AuthMode, JiraConfig};
 use crate::models::*;
-use std::env;
+use reqwest::StatusCode;
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum JiraError {
+    #[error("Jira rejected the credentials ({0}), check the jira auth settings")]
+    Auth(StatusCode),
+    #[error("Could not get an OAuth 2.0 access token ({status}): {body}")]
+    AccessToken { status: StatusCode, body: String },
+    #[error("Rate limited by Jira")]
+    RateLimited { retry_after: Option<Duration> },
+    #[error("Jira server error ({0})")]
+    Server(StatusCode),
+    #[error("Not found in Jira: {0}")]
+    NotFound(String),
+    #[error("Unexpected response from Jira ({status}): {body}")]
+    UnexpectedStatus { status: StatusCode, body: String },
+    #[error("Could not reach Jira: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("Could not parse Jira response: {0}")]
+    Deserialize(#[from] serde_json::Error),
+    #[error("{0}")]
+    Cassette(#[from] CassetteError),
+}
+
+impl JiraError {
+    fn is_retryable(&self) -> bool {
+        match self {
+            JiraError::RateLimited { .. } | JiraError::Server(_) => true,
+            JiraError::Request(e) => e.is_timeout() || e.is_connect(),
+            _ => false,
+        }
+    }
+}
+
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    fn from_config(config: &JiraConfig) -> Self {
+        Self {
+            max_retries: config.max_retries,
+            base_delay: Duration::from_millis(config.retry_base_delay_ms),
+            max_delay: Duration::from_millis(config.retry_max_delay_ms),
+        }
+    }
+
+    /// Exponential backoff, unless Jira told us how long to wait. `None` when that is longer than
+    /// `max_delay`, as retrying any earlier would only be rate limited again.
+    fn delay(&self, attempt: u32, error: &JiraError) -> Option<Duration> {
+        match error {
+            JiraError::RateLimited {
+                retry_after: Some(retry_after),
+            } => (*retry_after <= self.max_delay).then_some(*retry_after),
+            _ => Some(
+                self.base_delay
+                    .saturating_mul(2u32.saturating_pow(attempt))
+                    .min(self.max_delay),
+            ),
+        }
+    }
+}
+
+/// Parses a `Retry-After` header, given either in seconds or as an HTTP date.
+fn parse_retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    let value = headers.get(reqwest::header::RETRY_AFTER)?.to_str().ok()?;
+    if let Ok(seconds) = value.trim().parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    (date.with_timezone(&chrono::Utc) - chrono::Utc::now()).to_std().ok()
+}
+
+struct AccessToken {
+    token: String,
+    expires_at: tokio::time::Instant,
+}
+
+enum Auth {
+    Bearer(String),
+    Basic {
+        username: String,
+        token: String,
+    },
+    OAuth2 {
+        token_url: String,
+        client_id: String,
+        client_secret: String,
+        access_token: tokio::sync::Mutex<Option<AccessToken>>,
+    },
+}
+
+#[derive(serde::Deserialize)]
+struct AccessTokenResponse {
+    access_token: String,
+    expires_in: u64,
+}
+
+/// Access tokens are renewed this long before they expire.
+const ACCESS_TOKEN_EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+
+/// How issues are linked to their epic.
+pub enum EpicRelation {
+    /// A custom field holding the epic key, like the Epic Link field of company-managed projects.
+    Field(String),
+    /// The parent of the issue, as in team-managed and newer Jira Cloud projects.
+    Parent,
+}
 
 pub struct JiraClient {
     client: reqwest::Client,
     base_url: String,
+    api_version: u32,
+    auth: Auth,
+    retry_policy: RetryPolicy,
+    /// Records every response, or answers requests from recorded responses instead of Jira.
+    cassette: Option<Cassette>,
+    /// Field ID, field name or `parent`, as configured.
+    epic_link_field: String,
+    epic_relation: tokio::sync::OnceCell<EpicRelation>,
+    /// Field ID or name of the story points field, empty when story points are not tracked.
+    story_points_field: String,
+    story_points_field_id: tokio::sync::OnceCell<Option<String>>,
+    /// Field ID or name of the sprint field, empty when sprints are not tracked.
+    sprint_field: String,
+    sprint_field_id: tokio::sync::OnceCell<Option<String>>,
+}
+
+/// The custom fields `get_jql` reads values from, resolved once.
+struct ResolvedFields<'a> {
+    epic_relation: &'a EpicRelation,
+    story_points_field_id: Option<&'a str>,
+    sprint_field_id: Option<&'a str>,
 }
 
 impl JiraClient {
-    pub fn new() -> Self {
-        let mut headers = reqwest::header::HeaderMap::new();
-        headers.insert(
-            reqwest::header::AUTHORIZATION,
-            format!("Bearer {}", env::var("JIRA_TOKEN").expect("JIRA_TOKEN must be set"))
-                .parse()
-                .unwrap(),
-        );
+    pub fn new(config: &JiraConfig) -> Self {
+        let auth = match config.auth {
+            AuthMode::Bearer => Auth::Bearer(config.token.clone()),
+            AuthMode::Basic => Auth::Basic {
+                username: config.username.clone(),
+                token: config.token.clone(),
+            },
+            AuthMode::OAuth2 => Auth::OAuth2 {
+                token_url: config.oauth_token_url.clone(),
+                client_id: config.oauth_client_id.clone(),
+                client_secret: config.oauth_client_secret.clone(),
+                access_token: tokio::sync::Mutex::new(None),
+            },
+        };
 
-        let base_url = env::var("JIRA_BASE_URL").expect("JIRA_BASE_URL must be set");
+        let base_url = config.base_url.trim_end_matches('/').to_string();
 
         Self {
-            client: reqwest::Client::builder()
-                .default_headers(headers)
-                .build()
-                .unwrap(),
+            client: reqwest::Client::new(),
             base_url,
+            api_version: config.api_version,
+            auth,
+            retry_policy: RetryPolicy::from_config(config),
+            cassette: Cassette::new(&config.cassette_dir, config.cassette_mode),
+            epic_link_field: config.epic_link_field.clone(),
+            epic_relation: tokio::sync::OnceCell::new(),
+            story_points_field: config.story_points_field.clone(),
+            story_points_field_id: tokio::sync::OnceCell::new(),
+            sprint_field: config.sprint_field.clone(),
+            sprint_field_id: tokio::sync::OnceCell::new(),
         }
     }
 
-    async fn _get<T: serde::de::DeserializeOwned>(&self, path: &str) -> Result<T, String> {
-        let url = format!("{}{}", self.base_url, path);
-        self.client
-            .get(url)
+    /// Path of a REST API resource in the configured API version, e.g. `/rest/api/3/myself`.
+    fn api_path(&self, resource: &str) -> String {
+        format!("/rest/api/{}/{}", self.api_version, resource)
+    }
+
+    /// `api_path` with percent-encoded query parameters, which JQL needs for its quotes and `&`s.
+    fn api_path_with_query(&self, resource: &str, query: &[(&str, &str)]) -> String {
+        let mut url = reqwest::Url::parse("http://jira").expect("Static URL is valid");
+        url.query_pairs_mut().extend_pairs(query);
+        format!("{}?{}", self.api_path(resource), url.query().unwrap_or_default())
+    }
+
+    /// Returns a cached OAuth 2.0 access token, requesting a new one when it is about to expire.
+    async fn oauth2_access_token(
+        &self,
+        token_url: &str,
+        client_id: &str,
+        client_secret: &str,
+        access_token: &tokio::sync::Mutex<Option<AccessToken>>,
+    ) -> Result<String, JiraError> {
+        let mut access_token = access_token.lock().await;
+        if let Some(cached) = access_token.as_ref() {
+            if cached.expires_at > tokio::time::Instant::now() + ACCESS_TOKEN_EXPIRY_MARGIN {
+                return Ok(cached.token.clone());
+            }
+        }
+
+        let response = self
+            .client
+            .post(token_url)
+            .json(&serde_json::json!({
+                "grant_type": "client_credentials",
+                "client_id": client_id,
+                "client_secret": client_secret,
+            }))
             .send()
+            .await?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(JiraError::AccessToken {
+                status,
+                body: response.text().await.unwrap_or_default(),
+            });
+        }
+        let token_response: AccessTokenResponse = serde_json::from_slice(&response.bytes().await?)?;
+
+        *access_token = Some(AccessToken {
+            token: token_response.access_token.clone(),
+            expires_at: tokio::time::Instant::now() + Duration::from_secs(token_response.expires_in),
+        });
+        Ok(token_response.access_token)
+    }
+
+    async fn authorize(&self, request: reqwest::RequestBuilder) -> Result<reqwest::RequestBuilder, JiraError> {
+        Ok(match &self.auth {
+            Auth::Bearer(token) => request.bearer_auth(token),
+            Auth::Basic { username, token } => request.basic_auth(username, Some(token)),
+            Auth::OAuth2 {
+                token_url,
+                client_id,
+                client_secret,
+                access_token,
+            } => request.bearer_auth(
+                self.oauth2_access_token(token_url, client_id, client_secret, access_token)
+                    .await?,
+            ),
+        })
+    }
+
+    /// Drops a cached OAuth 2.0 access token after Jira rejected it, so the next request gets a new one.
+    async fn forget_access_token(&self) {
+        if let Auth::OAuth2 { access_token, .. } = &self.auth {
+            *access_token.lock().await = None;
+        }
+    }
+
+    async fn _request_once<T: serde::de::DeserializeOwned>(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        body: Option<&serde_json::Value>,
+    ) -> Result<T, JiraError> {
+        let (status, headers, response_body) = match &self.cassette {
+            Some(cassette) if cassette.is_replay() => {
+                let interaction = cassette.replay(&method, path, body)?;
+                (
+                    interaction.status(),
+                    interaction.response_headers(),
+                    interaction.response_body(),
+                )
+            }
+            cassette => {
+                let url = format!("{}{}", self.base_url, path);
+                let mut request = self.client.request(method.clone(), url);
+                if let Some(body) = body {
+                    request = request.json(body);
+                }
+                let request = self.authorize(request).await?.build()?;
+                let request_headers = request.headers().clone();
+                let response = self.client.execute(request).await?;
+
+                let status = response.status();
+                let headers = response.headers().clone();
+                let response_body = response.bytes().await?.to_vec();
+                if let Some(cassette) = cassette {
+                    cassette.record(&Interaction::new(
+                        &method,
+                        path,
+                        body,
+                        &request_headers,
+                        status,
+                        &headers,
+                        &response_body,
+                    ))?;
+                }
+                (status, headers, response_body)
+            }
+        };
+
+        match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+                self.forget_access_token().await;
+                return Err(JiraError::Auth(status));
+            }
+            StatusCode::TOO_MANY_REQUESTS => {
+                return Err(JiraError::RateLimited {
+                    retry_after: parse_retry_after(&headers),
+                })
+            }
+            StatusCode::NOT_FOUND => return Err(JiraError::NotFound(path.to_string())),
+            _ if status.is_server_error() => return Err(JiraError::Server(status)),
+            _ if !status.is_success() => {
+                return Err(JiraError::UnexpectedStatus {
+                    status,
+                    body: String::from_utf8_lossy(&response_body).into_owned(),
+                })
+            }
+            _ => {}
+        }
+
+        Ok(serde_json::from_slice(&response_body)?)
+    }
+
+    async fn _request<T: serde::de::DeserializeOwned>(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        body: Option<&serde_json::Value>,
+    ) -> Result<T, JiraError> {
+        let mut attempt = 0;
+        loop {
+            match self._request_once(method.clone(), path, body).await {
+                Err(e) if e.is_retryable() && attempt < self.retry_policy.max_retries => {
+                    let Some(delay) = self.retry_policy.delay(attempt, &e) else {
+                        return Err(e);
+                    };
+                    println!("{} on {}, retrying in {:?}...", e, path, delay);
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn _get<T: serde::de::DeserializeOwned>(&self, path: &str) -> Result<T, JiraError> {
+        self._request(reqwest::Method::GET, path, None).await
+    }
+
+    async fn _post<T: serde::de::DeserializeOwned>(&self, path: &str, body: &serde_json::Value) -> Result<T, JiraError> {
+        self._request(reqwest::Method::POST, path, Some(body)).await
+    }
+
+    pub async fn get_myself(&self) -> Result<JiraUser, JiraError> {
+        self._get(&self.api_path("myself")).await
+    }
+
+    pub async fn get_fields(&self) -> Result<Vec<JiraField>, JiraError> {
+        self._get(&self.api_path("field")).await
+    }
+
+    pub async fn get_issue(&self, issue_key: &str) -> Result<JiraIssue, JiraError> {
+        let issue = self._get(&self.api_path(&format!("issue/{}", issue_key))).await?;
+        self.resolve_issue(issue).await
+    }
+
+    /// Fills in the epic, story points and sprints of an issue obtained elsewhere, e.g. from a webhook.
+    pub async fn resolve_issue(&self, issue: JiraIssue) -> Result<JiraIssue, JiraError> {
+        let fields = self.resolved_fields().await?;
+        Ok(Self::resolve_custom_fields(&fields, issue))
+    }
+
+    pub async fn get_issue_with_changelog(&self, issue_key: &str) -> Result<IssueWithChangelog, JiraError> {
+        let mut issue = self
+            ._get::<IssueWithChangelog>(&self.api_path(&format!("issue/{}?fields=created,subtasks", issue_key)))
+            .await?;
+
+        loop {
+            let changelog_response = self
+                ._get::<ChangelogPage>(&self.api_path(&format!(
+                    "issue/{}/changelog?startAt={}",
+                    issue_key,
+                    issue.changelog.histories.len()
+                )))
+                .await?;
+
+            let page_size = changelog_response.values.len() as u64;
+            issue.changelog.histories.extend(changelog_response.values);
+
+            if page_size == 0
+                || changelog_response.is_last
+                || changelog_response.start_at + page_size >= changelog_response.total
+            {
+                break;
+            }
+        }
+
+        Ok(issue)
+    }
+
+    pub async fn get_worklogs(&self, issue_key: &str) -> Result<Vec<Worklog>, JiraError> {
+        let mut worklogs: Vec<Worklog> = Vec::new();
+
+        loop {
+            let worklogs_response = self
+                ._get::<WorklogListResponse>(&self.api_path(&format!(
+                    "issue/{}/worklog?startAt={}",
+                    issue_key,
+                    worklogs.len()
+                )))
+                .await?;
+
+            let page_size = worklogs_response.worklogs.len() as u64;
+            worklogs.extend(worklogs_response.worklogs);
+
+            if page_size == 0 || worklogs_response.start_at + page_size >= worklogs_response.total {
+                break;
+            }
+        }
+
+        Ok(worklogs)
+    }
+
+    /// Looks up the ID of a field by its name, unless it is already given as `customfield_...`.
+    async fn find_field_id(&self, field: &str) -> Result<String, JiraError> {
+        if field.starts_with("customfield_") {
+            return Ok(field.to_string());
+        }
+
+        let found = self
+            .get_fields()
+            .await?
+            .into_iter()
+            .find(|candidate| candidate.name.eq_ignore_ascii_case(field))
+            .ok_or_else(|| JiraError::NotFound(format!("field named {:?}", field)))?;
+        println!("Using field {} for {:?}", found.id, field);
+        Ok(found.id)
+    }
+
+    /// Resolves the configured epic link field, which is either `parent` or a custom field.
+    pub async fn epic_relation(&self) -> Result<&EpicRelation, JiraError> {
+        self.epic_relation
+            .get_or_try_init(|| async {
+                if self.epic_link_field == "parent" {
+                    return Ok(EpicRelation::Parent);
+                }
+                Ok(EpicRelation::Field(self.find_field_id(&self.epic_link_field).await?))
+            })
             .await
-            .map_err(|e| e.to_string())?
-            .json::<T>()
-            .await
-            .map_err(|e| e.to_string())
     }
 
-    pub async fn get_myself(&self) -> Result<JiraUser, String> {
-        self._get("/rest/api/2/myself").await
+    /// Resolves an optional field once, `None` when it is not configured or does not exist.
+    async fn optional_field_id<'a>(
+        &self,
+        field: &str,
+        field_id: &'a tokio::sync::OnceCell<Option<String>>,
+    ) -> Result<Option<&'a str>, JiraError> {
+        let field_id = field_id
+            .get_or_try_init(|| async {
+                if field.is_empty() {
+                    return Ok(None);
+                }
+                match self.find_field_id(field).await {
+                    Ok(id) => Ok(Some(id)),
+                    Err(JiraError::NotFound(_)) => {
+                        println!("Jira has no field {:?}, it is not collected", field);
+                        Ok(None)
+                    }
+                    Err(e) => Err(e),
+                }
+            })
+            .await?;
+        Ok(field_id.as_deref())
+    }
+
+    async fn resolved_fields(&self) -> Result<ResolvedFields<'_>, JiraError> {
+        Ok(ResolvedFields {
+            epic_relation: self.epic_relation().await?,
+            story_points_field_id: self
+                .optional_field_id(&self.story_points_field, &self.story_points_field_id)
+                .await?,
+            sprint_field_id: self
+                .optional_field_id(&self.sprint_field, &self.sprint_field_id)
+                .await?,
+        })
+    }
+
+    /// All sprints of an agile board.
+    pub async fn get_board_sprints(&self, board_id: u64) -> Result<Vec<Sprint>, JiraError> {
+        let mut sprints: Vec<Sprint> = Vec::new();
+
+        loop {
+            let sprints_response = self
+                ._get::<SprintListResponse>(&format!(
+                    "/rest/agile/1.0/board/{}/sprint?startAt={}",
+                    board_id,
+                    sprints.len()
+                ))
+                .await?;
+
+            let page_size = sprints_response.values.len();
+            sprints.extend(sprints_response.values.into_iter().map(normalize_sprint));
+
+            if page_size == 0 || sprints_response.is_last {
+                break;
+            }
+        }
+
+        Ok(sprints)
+    }
+
+    /// JQL clause matching issues linked to one of the given epics, which must not be empty.
+    pub async fn epic_link_jql(&self, epic_keys: &[String]) -> Result<String, JiraError> {
+        let field = match self.epic_relation().await? {
+            EpicRelation::Parent => "parent".to_string(),
+            EpicRelation::Field(id) => match id.strip_prefix("customfield_") {
+                Some(number) => format!("cf[{}]", number),
+                None => id.clone(),
+            },
+        };
+        Ok(format!("{} IN ({})", field, epic_keys.join(",")))
     }
 
-    pub async fn get_jql(&self, jql: &str) -> Result<Vec<JiraIssue>, String> {
+    /// Number of issues matching the query, without fetching them. API version 3 only gives an
+    /// approximation.
+    pub async fn count_jql(&self, jql: &str) -> Result<u64, JiraError> {
+        if self.api_version >= 3 {
+            let count_response = self
+                ._post::<ApproximateCountResponse>(
+                    &self.api_path("search/approximate-count"),
+                    &serde_json::json!({ "jql": jql }),
+                )
+                .await?;
+            return Ok(count_response.count);
+        }
+
+        let issues_response = self
+            ._get::<IssueListResponse>(&self.api_path_with_query("search", &[("jql", jql), ("maxResults", "0")]))
+            .await?;
+        Ok(issues_response.total)
+    }
+
+    fn resolve_custom_fields(fields: &ResolvedFields, mut issue: JiraIssue) -> JiraIssue {
+        let epic_key = match fields.epic_relation {
+            EpicRelation::Field(id) => issue.fields.other.get(id),
+            EpicRelation::Parent => issue
+                .fields
+                .other
+                .get("parent")
+                .and_then(|parent| parent.get("key")),
+        };
+        issue.fields.epic_key = epic_key.and_then(|value| value.as_str()).map(str::to_string);
+        issue.fields.story_points = fields
+            .story_points_field_id
+            .and_then(|id| issue.fields.other.get(id))
+            .and_then(|value| value.as_f64());
+        issue.fields.sprints = fields
+            .sprint_field_id
+            .and_then(|id| issue.fields.other.get(id))
+            .and_then(|value| value.as_array())
+            .map(|values| values.iter().filter_map(parse_sprint).collect())
+            .unwrap_or_default();
+        issue
+    }
+
+    pub async fn get_jql(&self, jql: &str) -> Result<Vec<JiraIssue>, JiraError> {
+        let fields = self.resolved_fields().await?;
+        if self.api_version >= 3 {
+            return self.get_jql_with_page_tokens(jql, &fields).await;
+        }
+
         let mut issues: Vec<JiraIssue> = Vec::new();
         let mut start_at = 0;
 
         loop {
             let issues_response = self
-                ._get::<IssueListResponse>(&format!(
-                    "/rest/api/2/search?jql={}&startAt={}",
-                    jql, start_at
+                ._get::<IssueListResponse>(&self.api_path_with_query(
+                    "search",
+                    &[("jql", jql), ("startAt", &start_at.to_string())],
                 ))
                 .await?;
 
             let total = issues_response.total;
             let page_size = issues_response.issues.len();
 
-            issues.extend(issues_response.issues);
+            issues.extend(
+                issues_response
+                    .issues
+                    .into_iter()
+                    .map(|issue| Self::resolve_custom_fields(&fields, issue)),
+            );
             start_at += page_size;
 
-            if start_at >= total.try_into().unwrap() {
+            if page_size == 0 || start_at as u64 >= total {
                 break;
             }
         }
 
         Ok(issues)
     }
+
+    /// The `search/jql` endpoint of API version 3, which pages with `nextPageToken` instead of `startAt`.
+    async fn get_jql_with_page_tokens(
+        &self,
+        jql: &str,
+        fields: &ResolvedFields<'_>,
+    ) -> Result<Vec<JiraIssue>, JiraError> {
+        let mut issues: Vec<JiraIssue> = Vec::new();
+        let mut next_page_token: Option<String> = None;
+
+        loop {
+            let mut query = vec![("jql", jql), ("fields", "*navigable")];
+            if let Some(token) = &next_page_token {
+                query.push(("nextPageToken", token));
+            }
+            let issues_response = self
+                ._get::<TokenPagedIssueListResponse>(&self.api_path_with_query("search/jql", &query))
+                .await?;
+
+            let page_size = issues_response.issues.len();
+            issues.extend(
+                issues_response
+                    .issues
+                    .into_iter()
+                    .map(|issue| Self::resolve_custom_fields(fields, issue)),
+            );
+
+            next_page_token = issues_response.next_page_token;
+            if page_size == 0 || issues_response.is_last || next_page_token.is_none() {
+                break;
+            }
+        }
+
+        Ok(issues)
+    }
+}
+
+/// Parses timestamps as returned by Jira, e.g. `2024-01-15T10:23:45.000+0100`.
+pub fn parse_jira_datetime(value: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    chrono::DateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S%.f%z")
+        .ok()
+        .map(|timestamp| timestamp.with_timezone(&chrono::Utc))
+}
+
+fn normalize_sprint_date(value: Option<String>) -> Option<String> {
+    let value = value?;
+    parse_jira_datetime(&value)
+        .or_else(|| {
+            chrono::DateTime::parse_from_rfc3339(&value)
+                .ok()
+                .map(|date| date.with_timezone(&chrono::Utc))
+        })
+        .map(|date| date.to_rfc3339())
+}
+
+fn normalize_sprint(sprint: Sprint) -> Sprint {
+    Sprint {
+        state: sprint.state.to_lowercase(),
+        start_date: normalize_sprint_date(sprint.start_date),
+        end_date: normalize_sprint_date(sprint.end_date),
+        complete_date: normalize_sprint_date(sprint.complete_date),
+        ..sprint
+    }
+}
+
+/// Parses a value of the sprint field, an object on Jira Cloud and a string like
+/// `com.atlassian.greenhopper.service.sprint.Sprint@1a2b[id=1,rapidViewId=2,state=ACTIVE,name=Sprint 1,...]`
+/// on Jira Server/Data Center.
+fn parse_sprint(value: &serde_json::Value) -> Option<Sprint> {
+    let sprint = match value {
+        serde_json::Value::Object(_) => serde_json::from_value::<Sprint>(value.clone()).ok()?,
+        serde_json::Value::String(value) => {
+            let properties = value.split_once('[')?.1.strip_suffix(']')?;
+
+            // Values such as the name may contain commas, so a part without `key=` continues the previous value.
+            let mut fields: Vec<(&str, String)> = Vec::new();
+            for part in properties.split(',') {
+                match part.split_once('=') {
+                    Some((key, value)) if !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric()) => {
+                        fields.push((key, value.to_string()))
+                    }
+                    _ => {
+                        let (_, previous) = fields.last_mut()?;
+                        previous.push(',');
+                        previous.push_str(part);
+                    }
+                }
+            }
+            let field = |key: &str| {
+                fields
+                    .iter()
+                    .find(|(name, _)| *name == key)
+                    .map(|(_, value)| value.clone())
+                    .filter(|value| value != "<null>")
+            };
+
+            Sprint {
+                id: field("id")?.parse().ok()?,
+                name: field("name").unwrap_or_default(),
+                state: field("state").unwrap_or_default(),
+                start_date: field("startDate"),
+                end_date: field("endDate"),
+                complete_date: field("completeDate"),
+                board_id: field("rapidViewId").and_then(|id| id.parse().ok()),
+            }
+        }
+        _ => return None,
+    };
+    Some(normalize_sprint(sprint))
 }
@@ -1,12 +1,31 @@
+mod backfill;
+mod cassette;
+mod cli;
 mod collector;
+mod config;
 mod database;
+mod diagnostics;
+mod export;
+mod forecast;
+mod forecast_graph;
+mod health;
 mod jira_client;
+mod metrics;
+mod migrations;
+mod mock_jira;
 mod models;
+mod monte_carlo;
 mod renderer;
+mod report;
 mod routes;
+mod sprints;
 mod time_graph;
+mod webhook;
 
+use clap::Parser;
+use cli::{Cli, Command, ExportFormat, ReportFormat};
 use collector::*;
+use config::Config;
 use database::*;
 use jira_client::*;
 use tower_http::services::ServeDir;
@@ -14,49 +33,231 @@ use tower_http::services::ServeDir;
 #[derive(Clone)]
 pub struct AppState {
     database: std::sync::Arc<Database>,
+    jira_client: std::sync::Arc<JiraClient>,
+    config: std::sync::Arc<Config>,
+    /// Held while collecting or applying webhooks so neither overlaps with the background collector.
+    /// `None` when the collector is disabled and the data must not be changed.
+    sync_lock: Option<std::sync::Arc<tokio::sync::Mutex<()>>>,
 }
 
 #[tokio::main]
 async fn main() {
     dotenv::dotenv().ok();
 
-    tokio::spawn(async {
-        run_data_collector().await;
+    let cli = Cli::parse();
+    let command = cli.command.unwrap_or(Command::Serve { no_collector: false });
+
+    let mut config = match Config::load(command.requires_jira() && cli.fixtures.is_none()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprint!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    if let Some(fixtures_dir) = &cli.fixtures {
+        serve_fixtures(&mut config, fixtures_dir).await;
+    }
+
+    match command {
+        Command::Serve { no_collector } => serve(config, !no_collector).await,
+        Command::Collect { epic } => run_collect_once(&config, epic.as_deref()).await,
+        Command::Export { format, epic, output } => {
+            run_export(&config, format, epic.as_deref(), output.as_deref()).await
+        }
+        Command::Report { format } => print_report(&config, format).await,
+        Command::CheckConfig => println!("Configuration is valid"),
+        Command::Doctor => run_doctor(&config).await,
+        Command::Backfill { epic } => run_backfill(&config, epic.as_deref()).await,
+        Command::SchemaVersion => print_schema_version(&config).await,
+    }
+}
+
+/// Starts the stand-in Jira on a free local port and points the Jira settings at it.
+async fn serve_fixtures(config: &mut Config, fixtures_dir: &std::path::Path) {
+    let fixtures = match mock_jira::Fixtures::load(fixtures_dir) {
+        Ok(fixtures) => fixtures,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("Could not bind the fixtures server");
+    let address = listener.local_addr().expect("Could not get the fixtures server address");
+    let mock = std::sync::Arc::new(mock_jira::MockJira::new(fixtures, 50));
+    tokio::spawn(async move {
+        if let Err(e) = mock.serve(listener).await {
+            println!("Fixtures server stopped: {}", e);
+        }
     });
+    println!("Serving Jira from fixtures in {} at {}", fixtures_dir.display(), address);
+
+    config.jira.base_url = format!("http://{}", address);
+    config.jira.api_version = 2;
+    config.jira.auth = config::AuthMode::Bearer;
+    config.jira.token = "fixtures".to_string();
+    for jql in [&mut config.jira.epics_jql, &mut config.jira.issues_jql] {
+        if jql.trim().is_empty() {
+            *jql = "project IS NOT EMPTY".to_string();
+        }
+    }
+}
+
+async fn serve(config: Config, with_collector: bool) {
+    let sync_lock = with_collector.then(|| std::sync::Arc::new(tokio::sync::Mutex::new(())));
+    if let Some(sync_lock) = &sync_lock {
+        let collector_config = config.clone();
+        let sync_lock = sync_lock.clone();
+        tokio::spawn(async move {
+            run_data_collector(&collector_config, &sync_lock).await;
+        });
+    }
 
     let state = AppState {
         database: std::sync::Arc::new(
-            Database::new()
+            Database::new(&config.database)
                 .await
                 .expect("Could not create database for collector"),
         ),
+        jira_client: std::sync::Arc::new(JiraClient::new(&config.jira)),
+        config: std::sync::Arc::new(config.clone()),
+        sync_lock,
     };
 
     let app = axum::Router::new()
         .route("/", axum::routing::get(routes::root))
         .route("/issue/{issue_key}/time_graph.svg", axum::routing::get(routes::issue_svg))
-        .nest_service("/static", ServeDir::new("src/static"))
+        .route("/epic/{epic_key}/time_graph.svg", axum::routing::get(routes::epic_svg))
+        .route("/epic/{epic_key}/forecast.svg", axum::routing::get(routes::epic_forecast_svg))
+        .route("/epic/{epic_key}/sprints", axum::routing::get(routes::epic_sprints))
+        .route("/sync", axum::routing::post(routes::sync_button))
+        .nest("/api/v1", routes::api_router())
+        .nest_service("/static", ServeDir::new(&config.server.static_dir))
+        .layer(axum::middleware::from_fn(routes::render_errors))
         .with_state(state);
 
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:8080")
+    let listener = tokio::net::TcpListener::bind(&config.server.bind_address)
         .await
-        .expect("Could not bind to port 8080");
+        .unwrap_or_else(|e| panic!("Could not bind to {}: {}", config.server.bind_address, e));
     axum::serve(listener, app)
         .await
         .expect("Could not start server");
 }
 
-async fn run_data_collector() {
-    let jira_client = JiraClient::new();
-    let database = Database::new()
+async fn run_data_collector(config: &Config, sync_lock: &tokio::sync::Mutex<()>) {
+    let jira_client = JiraClient::new(&config.jira);
+    let database = Database::new(&config.database)
         .await
         .expect("Could not create database for collector");
+    if let Err(e) = compact_snapshots_once(&database).await {
+        println!("Error compacting issue snapshots: {}", e);
+    }
     loop {
-        if let Err(e) = collect_data(&jira_client, &database).await {
+        let sync_guard = sync_lock.lock().await;
+        if let Err(e) = collect_data(&jira_client, &database, config, None).await {
             println!("Error collecting data: {}", e);
         }
+        drop(sync_guard);
+
+        println!("Sleeping for {} seconds...", config.collector.interval_secs);
+        tokio::time::sleep(tokio::time::Duration::from_secs(config.collector.interval_secs)).await;
+    }
+}
+
+async fn run_collect_once(config: &Config, epic_key: Option<&str>) {
+    let jira_client = JiraClient::new(&config.jira);
+    let database = Database::new(&config.database).await.expect("Could not create database");
+    if let Err(e) = compact_snapshots_once(&database).await {
+        println!("Error compacting issue snapshots: {}", e);
+    }
+    if let Err(e) = collect_data(&jira_client, &database, config, epic_key).await {
+        println!("Error collecting data: {}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn run_export(config: &Config, format: ExportFormat, epic_key: Option<&str>, output: Option<&std::path::Path>) {
+    let database = Database::new(&config.database).await.expect("Could not create database");
+    let (epics, issue_logs) = match epic_key {
+        Some(epic_key) => (
+            database
+                .get_epics()
+                .await
+                .expect("Could not get epics")
+                .into_iter()
+                .filter(|epic| epic.key == epic_key)
+                .collect(),
+            database.get_logs_of_epic(epic_key).await.expect("Could not get issues"),
+        ),
+        None => (
+            database.get_epics().await.expect("Could not get epics"),
+            database.get_all_issue_logs().await.expect("Could not get issues"),
+        ),
+    };
+
+    let content = match format {
+        ExportFormat::Json => export::to_json(&epics, &issue_logs).expect("Could not serialize export"),
+        ExportFormat::Csv => export::to_csv(&issue_logs),
+    };
+    match output {
+        Some(path) => {
+            if let Err(e) = std::fs::write(path, content) {
+                eprintln!("Could not write {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        }
+        None => print!("{}", content),
+    }
+}
+
+async fn print_report(config: &Config, format: ReportFormat) {
+    let database = Database::new(&config.database).await.expect("Could not create database");
+    let report = match report::build_report(&database, &config.health, chrono::Utc::now()).await {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("Error building report: {}", e);
+            std::process::exit(1);
+        }
+    };
+    match format {
+        ReportFormat::Text => print!("{}", report::to_text(&report)),
+        ReportFormat::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(&report).expect("Could not serialize report")
+        ),
+    }
+}
+
+async fn run_doctor(config: &Config) {
+    let jira_client = JiraClient::new(&config.jira);
+    let database = Database::open(&config.database).await.expect("Could not open database");
+    let diagnostics = diagnostics::run_diagnostics(&jira_client, &database, config).await;
+    print!("{}", diagnostics.to_text());
+    if !diagnostics.ok {
+        std::process::exit(1);
+    }
+}
+
+async fn print_schema_version(config: &Config) {
+    let database = Database::open(&config.database).await.expect("Could not open database");
+    let version = database
+        .get_schema_version()
+        .await
+        .expect("Could not get schema version");
+    let latest = migrations::latest_version();
+    println!("Schema version: {}", version);
+    println!("Latest version: {}", latest);
+    if version < latest {
+        println!("{} migration(s) pending, they are applied on the next start", latest - version);
+    }
+}
 
-        println!("Sleeping for 10 minutes...");
-        tokio::time::sleep(tokio::time::Duration::from_secs(10 * 60)).await;
+async fn run_backfill(config: &Config, epic_key: Option<&str>) {
+    let jira_client = JiraClient::new(&config.jira);
+    let database = Database::new(&config.database).await.expect("Could not create database");
+    if let Err(e) = backfill::backfill(&jira_client, &database, epic_key).await {
+        println!("Error backfilling data: {}", e);
+        std::process::exit(1);
     }
 }
@@ -0,0 +1,184 @@
+use crate::models::IssueLog;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+pub const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+pub struct EpicTotals {
+    pub date: i64,
+    pub time_spent: i64,
+    pub time_estimate: i64,
+}
+
+/// A value tracked per issue snapshot that can be plotted.
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    OriginalEstimate,
+    RemainingEstimate,
+    TimeSpent,
+    StoryPoints,
+}
+
+impl Metric {
+    pub fn of(&self, log: &IssueLog) -> f64 {
+        match self {
+            Metric::OriginalEstimate => log.time_estimate as f64,
+            Metric::RemainingEstimate => log.remaining_estimate as f64,
+            Metric::TimeSpent => log.time_spent as f64,
+            Metric::StoryPoints => log.story_points.unwrap_or(0.0),
+        }
+    }
+
+    /// Whether the metric is measured in seconds, so it can share an axis with other time metrics.
+    pub fn is_time(&self) -> bool {
+        !matches!(self, Metric::StoryPoints)
+    }
+}
+
+impl FromStr for Metric {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "original_estimate" => Ok(Metric::OriginalEstimate),
+            "remaining_estimate" => Ok(Metric::RemainingEstimate),
+            "time_spent" => Ok(Metric::TimeSpent),
+            "story_points" => Ok(Metric::StoryPoints),
+            _ => Err(format!(
+                "Unknown metric {:?}, expected original_estimate, remaining_estimate, time_spent or story_points",
+                value
+            )),
+        }
+    }
+}
+
+/// Totals of several metrics at one point in time, in the order they were requested.
+pub struct MetricTotals {
+    pub date: i64,
+    pub values: Vec<f64>,
+    /// Totals of the issues in the done status category.
+    pub done_values: Vec<f64>,
+}
+
+#[derive(Serialize)]
+pub struct EpicMetrics {
+    pub epic_key: String,
+    pub issue_count: usize,
+    pub done_issue_count: usize,
+    pub unestimated_issue_count: usize,
+    pub time_estimate: i64,
+    pub time_spent: i64,
+    pub time_remaining: i64,
+    /// Sum of the remaining estimates in Jira, unlike `time_remaining` which is derived from time spent.
+    pub remaining_estimate: i64,
+    pub story_points: f64,
+    /// Time spent as a percentage of the estimate, `None` when nothing is estimated.
+    pub percent_spent: Option<f64>,
+}
+
+impl EpicMetrics {
+    pub fn from_latest_logs(epic_key: &str, latest_logs: &[&IssueLog]) -> Self {
+        let time_estimate = latest_logs.iter().map(|log| log.time_estimate).sum::<i64>();
+        let time_spent = latest_logs.iter().map(|log| log.time_spent).sum::<i64>();
+
+        Self {
+            epic_key: epic_key.to_string(),
+            issue_count: latest_logs.len(),
+            done_issue_count: latest_logs.iter().filter(|log| log.is_done()).count(),
+            unestimated_issue_count: latest_logs
+                .iter()
+                .filter(|log| log.time_estimate == 0)
+                .count(),
+            time_estimate,
+            time_spent,
+            time_remaining: latest_logs
+                .iter()
+                .filter(|log| !log.is_done())
+                .map(|log| (log.time_estimate - log.time_spent).max(0))
+                .sum(),
+            remaining_estimate: latest_logs.iter().map(|log| log.remaining_estimate).sum(),
+            story_points: latest_logs
+                .iter()
+                .filter_map(|log| log.story_points)
+                .fold(0.0, |sum, points| sum + points),
+            percent_spent: (time_estimate > 0)
+                .then(|| time_spent as f64 / time_estimate as f64 * 100.0),
+        }
+    }
+}
+
+/// Calls `total` with the latest snapshot of every issue in the epic at each point in time where
+/// a snapshot was taken. Every issue counts with its last known values until a later snapshot
+/// shows it in another epic.
+fn totals_over_time<'a, T>(
+    epic_key: &str,
+    issue_logs: &'a [IssueLog],
+    total: impl Fn(i64, &[&'a IssueLog]) -> T,
+) -> Result<Vec<T>, chrono::ParseError> {
+    let logs_by_date = issue_logs
+        .iter()
+        .map(|log| {
+            let date = chrono::DateTime::parse_from_rfc3339(&log.updated_at)?.timestamp();
+            Ok((date, log))
+        })
+        .collect::<Result<Vec<_>, chrono::ParseError>>()?
+        .into_iter()
+        .sorted_by_key(|(date, _)| *date)
+        .chunk_by(|(date, _)| *date);
+
+    let mut latest_logs: HashMap<&str, &IssueLog> = HashMap::new();
+    let mut totals = Vec::new();
+    for (date, logs) in &logs_by_date {
+        for (_, log) in logs {
+            latest_logs.insert(&log.key, log);
+        }
+
+        let epic_logs = latest_logs
+            .values()
+            .copied()
+            .filter(|log| log.epic_key == epic_key)
+            .collect::<Vec<_>>();
+        totals.push(total(date, &epic_logs));
+    }
+    Ok(totals)
+}
+
+/// Sums the time tracking of all issues of an epic over time.
+pub fn epic_totals_over_time(
+    epic_key: &str,
+    issue_logs: &[IssueLog],
+) -> Result<Vec<EpicTotals>, chrono::ParseError> {
+    totals_over_time(epic_key, issue_logs, |date, epic_logs| EpicTotals {
+        date,
+        time_spent: epic_logs.iter().map(|log| log.time_spent).sum(),
+        time_estimate: epic_logs.iter().map(|log| log.time_estimate).sum(),
+    })
+}
+
+/// Sums the given metrics of all issues of an epic over time.
+pub fn epic_metric_totals_over_time(
+    epic_key: &str,
+    issue_logs: &[IssueLog],
+    metrics: &[Metric],
+) -> Result<Vec<MetricTotals>, chrono::ParseError> {
+    totals_over_time(epic_key, issue_logs, |date, epic_logs| MetricTotals {
+        date,
+        values: metrics
+            .iter()
+            .map(|metric| epic_logs.iter().map(|log| metric.of(log)).sum())
+            .collect(),
+        done_values: metrics
+            .iter()
+            .map(|metric| {
+                epic_logs
+                    .iter()
+                    .filter(|log| log.is_done())
+                    .map(|log| metric.of(log))
+                    .sum()
+            })
+            .collect(),
+    })
+}
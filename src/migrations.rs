@@ -0,0 +1,166 @@
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+// Migrations are applied in order and must never be edited once released, add a new one instead.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial_schema",
+        // Databases created before migrations existed already have (some of) these tables.
+        sql: "
+            CREATE TABLE IF NOT EXISTS epics (
+                key TEXT NOT NULL PRIMARY KEY,
+                summary TEXT NOT NULL,
+                updated_at DATETIME NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS issues (
+                key TEXT NOT NULL,
+                summary TEXT NOT NULL,
+                epic_key TEXT NOT NULL,
+                time_estimate INTEGER NOT NULL,
+                time_spent INTEGER NOT NULL,
+                updated_at DATETIME NOT NULL,
+                assignee TEXT NOT NULL,
+                FOREIGN KEY (epic_key) REFERENCES epics(key)
+            );
+            CREATE TABLE IF NOT EXISTS issue_last_seen (
+                key TEXT NOT NULL PRIMARY KEY,
+                last_seen_at DATETIME NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS sync_state (
+                name TEXT NOT NULL PRIMARY KEY,
+                value TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS issues_key_updated_at ON issues (key, updated_at);",
+    },
+    Migration {
+        version: 2,
+        name: "issue_status_fields",
+        // Labels are stored as a JSON array.
+        sql: "
+            ALTER TABLE issues ADD COLUMN status TEXT NOT NULL DEFAULT '';
+            ALTER TABLE issues ADD COLUMN status_category TEXT NOT NULL DEFAULT '';
+            ALTER TABLE issues ADD COLUMN issue_type TEXT NOT NULL DEFAULT '';
+            ALTER TABLE issues ADD COLUMN priority TEXT NOT NULL DEFAULT '';
+            ALTER TABLE issues ADD COLUMN resolution TEXT NOT NULL DEFAULT '';
+            ALTER TABLE issues ADD COLUMN resolution_date DATETIME;
+            ALTER TABLE issues ADD COLUMN labels TEXT NOT NULL DEFAULT '[]';",
+    },
+    Migration {
+        version: 3,
+        name: "issue_metric_fields",
+        sql: "
+            ALTER TABLE issues ADD COLUMN remaining_estimate INTEGER NOT NULL DEFAULT 0;
+            ALTER TABLE issues ADD COLUMN story_points REAL;",
+    },
+    Migration {
+        version: 4,
+        name: "sprints",
+        // Sprint IDs of each snapshot are stored as a JSON array.
+        sql: "
+            CREATE TABLE sprints (
+                id INTEGER NOT NULL PRIMARY KEY,
+                name TEXT NOT NULL,
+                state TEXT NOT NULL,
+                start_date DATETIME,
+                end_date DATETIME,
+                complete_date DATETIME,
+                board_id INTEGER
+            );
+            ALTER TABLE issues ADD COLUMN sprint_ids TEXT NOT NULL DEFAULT '[]';",
+    },
+    Migration {
+        version: 5,
+        name: "issue_removals",
+        sql: "
+            CREATE TABLE issue_removals (
+                key TEXT NOT NULL,
+                epic_key TEXT NOT NULL,
+                removed_at DATETIME NOT NULL,
+                reason TEXT NOT NULL
+            );
+            CREATE INDEX issue_removals_key_removed_at ON issue_removals (key, removed_at);
+            ALTER TABLE epics ADD COLUMN archived_at DATETIME;",
+    },
+    Migration {
+        version: 6,
+        name: "sync_runs",
+        sql: "
+            CREATE TABLE sync_runs (
+                id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+                started_at DATETIME NOT NULL,
+                finished_at DATETIME,
+                full_sync INTEGER NOT NULL,
+                epics_fetched INTEGER NOT NULL,
+                issues_fetched INTEGER NOT NULL,
+                snapshots_written INTEGER NOT NULL,
+                issues_removed INTEGER NOT NULL,
+                error TEXT
+            );",
+    },
+    Migration {
+        version: 7,
+        name: "sync_run_epic",
+        sql: "
+            ALTER TABLE sync_runs ADD COLUMN epic_key TEXT;",
+    },
+];
+
+pub fn latest_version() -> i64 {
+    MIGRATIONS.last().map(|migration| migration.version).unwrap_or(0)
+}
+
+fn ensure_version_table(conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS schema_version (
+            version INTEGER NOT NULL PRIMARY KEY,
+            name TEXT NOT NULL,
+            applied_at DATETIME NOT NULL
+        )",
+    )
+}
+
+pub fn current_version(conn: &rusqlite::Connection) -> rusqlite::Result<i64> {
+    ensure_version_table(conn)?;
+    conn.query_row(
+        "SELECT COALESCE(MAX(version), 0) FROM schema_version",
+        [],
+        |row| row.get(0),
+    )
+}
+
+/// Applies every pending migration, each in its own transaction. Returns the versions that were applied.
+pub fn apply_migrations(conn: &mut rusqlite::Connection) -> rusqlite::Result<Vec<i64>> {
+    ensure_version_table(conn)?;
+    let mut applied = Vec::new();
+
+    for migration in MIGRATIONS {
+        // Take the write lock before checking the version, another connection may be migrating concurrently.
+        let tx = conn.transaction_with_behavior(rusqlite::TransactionBehavior::Immediate)?;
+        let current: i64 = tx.query_row(
+            "SELECT COALESCE(MAX(version), 0) FROM schema_version",
+            [],
+            |row| row.get(0),
+        )?;
+        if migration.version <= current {
+            continue;
+        }
+
+        tx.execute_batch(migration.sql)?;
+        tx.execute(
+            "INSERT INTO schema_version (version, name, applied_at) VALUES (?, ?, ?)",
+            (
+                migration.version,
+                migration.name,
+                chrono::Utc::now().to_rfc3339(),
+            ),
+        )?;
+        tx.commit()?;
+        applied.push(migration.version);
+    }
+
+    Ok(applied)
+}
@@ -0,0 +1,488 @@
+//! A stand-in for the parts of the Jira REST API the collector uses, serving fixture JSON. Runs
+//! in-process for `--fixtures` and for the integration tests, which include this file directly,
+//! so it must not depend on the rest of the crate.
+
+use axum::extract::{Path, Query, State};
+use axum::http::{StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum FixtureError {
+    #[error("Could not read fixture {path}: {source}")]
+    Read {
+        path: String,
+        source: std::io::Error,
+    },
+    #[error("Could not parse fixture {path}: {source}")]
+    Parse {
+        path: String,
+        source: serde_json::Error,
+    },
+}
+
+/// Makes requests to paths starting with `path` fail with `status`, `times` times.
+#[derive(Deserialize, Clone, Debug)]
+pub struct Failure {
+    pub path: String,
+    pub status: u16,
+    #[serde(default = "default_failure_times")]
+    pub times: u32,
+    /// Seconds sent as `Retry-After` when rate limiting with status 429.
+    #[serde(default)]
+    pub retry_after: u64,
+}
+
+fn default_failure_times() -> u32 {
+    1
+}
+
+/// The data served, read from a directory with these files, all but the first two optional:
+///
+/// - `epics.json`: the epics, as Jira returns them from a search
+/// - `issues.json`: the issues of all epics
+/// - `myself.json`: the user the credentials belong to
+/// - `fields.json`: the fields, to look up custom fields by name
+/// - `changelogs.json`: the changelog histories by issue key
+/// - `worklogs.json`: the worklogs by issue key
+/// - `sprints.json`: the sprints by board ID
+/// - `failures.json`: failures to inject, see `Failure`
+#[derive(Default, Clone)]
+pub struct Fixtures {
+    pub myself: Value,
+    pub fields: Vec<Value>,
+    pub epics: Vec<Value>,
+    pub issues: Vec<Value>,
+    pub changelogs: HashMap<String, Vec<Value>>,
+    pub worklogs: HashMap<String, Vec<Value>>,
+    pub sprints: HashMap<String, Vec<Value>>,
+    pub failures: Vec<Failure>,
+}
+
+impl Fixtures {
+    pub fn load(dir: &std::path::Path) -> Result<Self, FixtureError> {
+        fn read<T: serde::de::DeserializeOwned>(
+            dir: &std::path::Path,
+            name: &str,
+            required: bool,
+        ) -> Result<Option<T>, FixtureError> {
+            let path = dir.join(name);
+            let content = match std::fs::read(&path) {
+                Ok(content) => content,
+                Err(e) if !required && e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+                Err(source) => {
+                    return Err(FixtureError::Read {
+                        path: path.display().to_string(),
+                        source,
+                    })
+                }
+            };
+            serde_json::from_slice(&content)
+                .map(Some)
+                .map_err(|source| FixtureError::Parse {
+                    path: path.display().to_string(),
+                    source,
+                })
+        }
+
+        Ok(Self {
+            myself: read(dir, "myself.json", false)?
+                .unwrap_or_else(|| json!({ "name": "fixtures", "displayName": "Fixtures" })),
+            fields: read(dir, "fields.json", false)?.unwrap_or_default(),
+            epics: read(dir, "epics.json", true)?.unwrap_or_default(),
+            issues: read(dir, "issues.json", true)?.unwrap_or_default(),
+            changelogs: read(dir, "changelogs.json", false)?.unwrap_or_default(),
+            worklogs: read(dir, "worklogs.json", false)?.unwrap_or_default(),
+            sprints: read(dir, "sprints.json", false)?.unwrap_or_default(),
+            failures: read(dir, "failures.json", false)?.unwrap_or_default(),
+        })
+    }
+
+    fn find_issue(&self, id_or_key: &str) -> Option<&Value> {
+        self.epics
+            .iter()
+            .chain(&self.issues)
+            .find(|issue| issue["key"] == id_or_key || issue["id"] == id_or_key)
+    }
+
+    fn changelog(&self, issue: &Value) -> Vec<&Value> {
+        issue["key"]
+            .as_str()
+            .and_then(|key| self.changelogs.get(key))
+            .map(|histories| histories.iter().collect())
+            .unwrap_or_default()
+    }
+
+    /// The epics or issues a query of the collector or a webhook matches. JQL is not evaluated,
+    /// only the clauses they add are: `type = Epic`, `key = ...` and the epic link `IN (...)`.
+    fn search(&self, jql: &str) -> Vec<&Value> {
+        let key = jql
+            .split("key = ")
+            .nth(1)
+            .and_then(|rest| rest.split_whitespace().next())
+            .map(|key| key.trim_matches('"'));
+        if jql.contains("type = Epic") {
+            return self
+                .epics
+                .iter()
+                .filter(|epic| key.is_none_or(|key| epic["key"] == key))
+                .collect();
+        }
+
+        let epic_keys = jql
+            .split_once(" IN (")
+            .and_then(|(_, rest)| rest.split_once(')'))
+            .map(|(keys, _)| keys.split(',').map(str::trim).collect::<Vec<_>>());
+        self.issues
+            .iter()
+            .filter(|issue| key.is_none_or(|key| issue["key"] == key))
+            .filter(|issue| {
+                let Some(epic_keys) = &epic_keys else {
+                    return true;
+                };
+                issue["fields"]
+                    .as_object()
+                    .into_iter()
+                    .flat_map(|fields| fields.values())
+                    .any(|value| {
+                        let key = value.get("key").unwrap_or(value);
+                        key.as_str().is_some_and(|key| epic_keys.contains(&key))
+                    })
+            })
+            .collect()
+    }
+}
+
+pub struct MockJira {
+    fixtures: RwLock<Fixtures>,
+    page_size: usize,
+    failures: Mutex<Vec<Failure>>,
+    requests: Mutex<Vec<String>>,
+    delay: Mutex<Duration>,
+}
+
+impl MockJira {
+    pub fn new(fixtures: Fixtures, page_size: usize) -> Self {
+        let mock = Self {
+            failures: Mutex::new(fixtures.failures.clone()),
+            requests: Mutex::new(Vec::new()),
+            delay: Mutex::new(Duration::ZERO),
+            page_size: page_size.max(1),
+            fixtures: RwLock::new(Fixtures::default()),
+        };
+        *mock.fixtures.write().unwrap() = fixtures;
+        mock
+    }
+
+    pub fn router(self: Arc<Self>) -> axum::Router {
+        axum::Router::new()
+            .route("/rest/api/{version}/myself", axum::routing::get(myself))
+            .route("/rest/api/{version}/field", axum::routing::get(fields))
+            .route("/rest/api/{version}/search", axum::routing::get(search))
+            .route(
+                "/rest/api/{version}/search/jql",
+                axum::routing::get(search_with_page_tokens),
+            )
+            .route(
+                "/rest/api/{version}/search/approximate-count",
+                axum::routing::post(approximate_count),
+            )
+            .route(
+                "/rest/api/{version}/issue/{issue}",
+                axum::routing::get(issue),
+            )
+            .route(
+                "/rest/api/{version}/issue/{issue}/worklog",
+                axum::routing::get(worklogs),
+            )
+            .route(
+                "/rest/api/{version}/issue/{issue}/changelog",
+                axum::routing::get(changelog),
+            )
+            .route(
+                "/rest/agile/1.0/board/{board_id}/sprint",
+                axum::routing::get(sprints),
+            )
+            .layer(axum::middleware::from_fn_with_state(
+                self.clone(),
+                inject_failures,
+            ))
+            .with_state(self)
+    }
+
+    /// Serves on `listener` until the process exits.
+    pub async fn serve(self: Arc<Self>, listener: tokio::net::TcpListener) -> std::io::Result<()> {
+        axum::serve(listener, self.router()).await
+    }
+}
+
+/// Controls for the integration tests.
+#[allow(dead_code)]
+impl MockJira {
+    /// Makes the next `times` requests to paths starting with `path` fail with `status`.
+    pub fn fail(&self, path: &str, status: u16, times: u32) {
+        self.failures.lock().unwrap().push(Failure {
+            path: path.to_string(),
+            status,
+            times,
+            retry_after: 0,
+        });
+    }
+
+    /// Makes the next `times` requests to paths starting with `path` fail with status 429,
+    /// asking to retry after `retry_after` seconds.
+    pub fn rate_limit(&self, path: &str, retry_after: u64, times: u32) {
+        self.failures.lock().unwrap().push(Failure {
+            path: path.to_string(),
+            status: 429,
+            times,
+            retry_after,
+        });
+    }
+
+    /// Path and query of every request received so far.
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Changes the data served from now on.
+    pub fn update(&self, change: impl FnOnce(&mut Fixtures)) {
+        change(&mut self.fixtures.write().unwrap());
+    }
+
+    /// Makes every response from now on take at least `delay`, e.g. to keep a sync running.
+    pub fn delay(&self, delay: Duration) {
+        *self.delay.lock().unwrap() = delay;
+    }
+}
+
+async fn inject_failures(
+    State(mock): State<Arc<MockJira>>,
+    uri: Uri,
+    request: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> Response {
+    let path_and_query = uri.path_and_query().map_or(uri.path(), |p| p.as_str());
+    mock.requests
+        .lock()
+        .unwrap()
+        .push(path_and_query.to_string());
+    let delay = *mock.delay.lock().unwrap();
+    tokio::time::sleep(delay).await;
+
+    let failure = {
+        let mut failures = mock.failures.lock().unwrap();
+        let failure = failures
+            .iter_mut()
+            .find(|failure| failure.times > 0 && uri.path().starts_with(&failure.path));
+        failure.map(|failure| {
+            failure.times -= 1;
+            failure.clone()
+        })
+    };
+    let Some(failure) = failure else {
+        return next.run(request).await;
+    };
+    match StatusCode::from_u16(failure.status) {
+        Ok(StatusCode::TOO_MANY_REQUESTS) => (
+            StatusCode::TOO_MANY_REQUESTS,
+            [("Retry-After", failure.retry_after.to_string())],
+        )
+            .into_response(),
+        Ok(status) => (
+            status,
+            Json(json!({ "errorMessages": ["Injected failure"] })),
+        )
+            .into_response(),
+        Err(_) => next.run(request).await,
+    }
+}
+
+#[derive(Deserialize)]
+struct PageQuery {
+    #[serde(rename = "startAt", default)]
+    start_at: usize,
+    #[serde(rename = "maxResults")]
+    max_results: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct SearchQuery {
+    #[serde(default)]
+    jql: String,
+    #[serde(rename = "startAt", default)]
+    start_at: usize,
+    #[serde(rename = "maxResults")]
+    max_results: Option<usize>,
+    #[serde(rename = "nextPageToken")]
+    next_page_token: Option<String>,
+}
+
+impl MockJira {
+    /// The page of `values` starting at `start_at`, at most `max_results` long.
+    fn page<'a>(
+        &self,
+        values: &[&'a Value],
+        start_at: usize,
+        max_results: Option<usize>,
+    ) -> Vec<&'a Value> {
+        let page_size = max_results.unwrap_or(self.page_size).min(self.page_size);
+        values
+            .iter()
+            .skip(start_at)
+            .take(page_size)
+            .copied()
+            .collect()
+    }
+}
+
+async fn myself(State(mock): State<Arc<MockJira>>) -> Json<Value> {
+    Json(mock.fixtures.read().unwrap().myself.clone())
+}
+
+async fn fields(State(mock): State<Arc<MockJira>>) -> Json<Value> {
+    Json(json!(mock.fixtures.read().unwrap().fields))
+}
+
+async fn search(
+    State(mock): State<Arc<MockJira>>,
+    Query(query): Query<SearchQuery>,
+) -> Json<Value> {
+    let fixtures = mock.fixtures.read().unwrap();
+    let issues = fixtures.search(&query.jql);
+    let page = mock.page(&issues, query.start_at, query.max_results);
+    Json(json!({
+        "startAt": query.start_at,
+        "maxResults": page.len(),
+        "total": issues.len(),
+        "issues": page,
+    }))
+}
+
+/// API version 3 search, whose page tokens are just the index of the next issue here.
+async fn search_with_page_tokens(
+    State(mock): State<Arc<MockJira>>,
+    Query(query): Query<SearchQuery>,
+) -> Json<Value> {
+    let fixtures = mock.fixtures.read().unwrap();
+    let issues = fixtures.search(&query.jql);
+    let start_at = query
+        .next_page_token
+        .and_then(|token| token.parse().ok())
+        .unwrap_or(0);
+    let page = mock.page(&issues, start_at, query.max_results);
+    let next = start_at + page.len();
+    Json(json!({
+        "issues": page,
+        "nextPageToken": (next < issues.len()).then(|| next.to_string()),
+        "isLast": next >= issues.len(),
+    }))
+}
+
+async fn approximate_count(
+    State(mock): State<Arc<MockJira>>,
+    Json(body): Json<Value>,
+) -> Json<Value> {
+    let fixtures = mock.fixtures.read().unwrap();
+    let count = fixtures
+        .search(body["jql"].as_str().unwrap_or_default())
+        .len();
+    Json(json!({ "count": count }))
+}
+
+#[derive(Deserialize)]
+struct IssueQuery {
+    expand: Option<String>,
+}
+
+async fn issue(
+    State(mock): State<Arc<MockJira>>,
+    Path((_, id_or_key)): Path<(String, String)>,
+    Query(query): Query<IssueQuery>,
+) -> Result<Json<Value>, StatusCode> {
+    let fixtures = mock.fixtures.read().unwrap();
+    let mut issue = fixtures
+        .find_issue(&id_or_key)
+        .ok_or(StatusCode::NOT_FOUND)?
+        .clone();
+    // Like Jira, only the first page of the changelog is expanded.
+    if query.expand.as_deref() == Some("changelog") {
+        let histories = fixtures.changelog(&issue);
+        issue["changelog"] = json!({
+            "startAt": 0,
+            "maxResults": mock.page_size,
+            "total": histories.len(),
+            "histories": mock.page(&histories, 0, None),
+        });
+    }
+    Ok(Json(issue))
+}
+
+async fn worklogs(
+    State(mock): State<Arc<MockJira>>,
+    Path((_, id_or_key)): Path<(String, String)>,
+    Query(query): Query<PageQuery>,
+) -> Result<Json<Value>, StatusCode> {
+    let fixtures = mock.fixtures.read().unwrap();
+    let issue = fixtures
+        .find_issue(&id_or_key)
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let worklogs = issue["key"]
+        .as_str()
+        .and_then(|key| fixtures.worklogs.get(key))
+        .map(|worklogs| worklogs.iter().collect::<Vec<_>>())
+        .unwrap_or_default();
+    let page = mock.page(&worklogs, query.start_at, query.max_results);
+    Ok(Json(json!({
+        "startAt": query.start_at,
+        "maxResults": page.len(),
+        "total": worklogs.len(),
+        "worklogs": page,
+    })))
+}
+
+async fn changelog(
+    State(mock): State<Arc<MockJira>>,
+    Path((_, id_or_key)): Path<(String, String)>,
+    Query(query): Query<PageQuery>,
+) -> Result<Json<Value>, StatusCode> {
+    let fixtures = mock.fixtures.read().unwrap();
+    let issue = fixtures
+        .find_issue(&id_or_key)
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let histories = fixtures.changelog(issue);
+    let page = mock.page(&histories, query.start_at, query.max_results);
+    Ok(Json(json!({
+        "startAt": query.start_at,
+        "maxResults": page.len(),
+        "total": histories.len(),
+        "isLast": query.start_at + page.len() >= histories.len(),
+        "values": page,
+    })))
+}
+
+async fn sprints(
+    State(mock): State<Arc<MockJira>>,
+    Path(board_id): Path<String>,
+    Query(query): Query<PageQuery>,
+) -> Result<Json<Value>, StatusCode> {
+    let fixtures = mock.fixtures.read().unwrap();
+    let sprints = fixtures
+        .sprints
+        .get(&board_id)
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let sprints = sprints.iter().collect::<Vec<_>>();
+    let page = mock.page(&sprints, query.start_at, query.max_results);
+    Ok(Json(json!({
+        "startAt": query.start_at,
+        "maxResults": page.len(),
+        "isLast": query.start_at + page.len() >= sprints.len(),
+        "values": page,
+    })))
+}
@@ -1,14 +1,36 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
 
+/// Jira Server/Data Center identifies users by `name`, Jira Cloud only by `accountId` and hides
+/// the email address depending on the user's privacy settings.
 #[derive(Deserialize)]
 pub struct JiraUser {
-    pub name: String,
+    pub name: Option<String>,
+    #[serde(rename = "accountId")]
+    pub account_id: Option<String>,
+    #[serde(rename = "displayName")]
+    pub display_name: Option<String>,
     #[serde(rename = "emailAddress")]
-    pub email: String,
+    pub email: Option<String>,
+}
+
+impl JiraUser {
+    /// The name stored for assignees: the user name where there is one, the display name otherwise.
+    pub fn identifier(&self) -> String {
+        self.name
+            .as_ref()
+            .or(self.display_name.as_ref())
+            .or(self.account_id.as_ref())
+            .cloned()
+            .unwrap_or_default()
+    }
 }
 
 #[derive(Deserialize)]
+#[allow(dead_code)]
 pub struct IssueListResponse {
+    #[serde(default)]
     pub expand: String,
     #[serde(rename = "startAt")]
     pub start_at: u64,
@@ -18,6 +40,26 @@ pub struct IssueListResponse {
     pub issues: Vec<JiraIssue>,
 }
 
+#[derive(Deserialize)]
+pub struct TokenPagedIssueListResponse {
+    pub issues: Vec<JiraIssue>,
+    #[serde(rename = "nextPageToken")]
+    pub next_page_token: Option<String>,
+    #[serde(rename = "isLast", default)]
+    pub is_last: bool,
+}
+
+#[derive(Deserialize)]
+pub struct ApproximateCountResponse {
+    pub count: u64,
+}
+
+#[derive(Deserialize)]
+pub struct JiraField {
+    pub id: String,
+    pub name: String,
+}
+
 #[derive(Deserialize)]
 pub struct JiraIssue {
     pub key: String,
@@ -27,28 +69,259 @@ pub struct JiraIssue {
 #[derive(Deserialize)]
 pub struct JiraIssueFields {
     pub summary: String,
-    #[serde(rename = "customfield_11100")]
+    /// Resolved from the configured epic link field by `JiraClient`.
+    #[serde(skip)]
     pub epic_key: Option<String>,
     #[serde(rename = "aggregatetimeoriginalestimate")]
     pub time_estimate: Option<u64>,
     #[serde(rename = "aggregatetimespent")]
     pub time_spent: Option<u64>,
+    #[serde(rename = "aggregatetimeestimate")]
+    pub remaining_estimate: Option<u64>,
+    /// Resolved from the configured story points field by `JiraClient`.
+    #[serde(skip)]
+    pub story_points: Option<f64>,
+    /// Resolved from the configured sprint field by `JiraClient`.
+    #[serde(skip)]
+    pub sprints: Vec<Sprint>,
     pub assignee: Option<JiraUser>,
+    pub status: Option<JiraStatus>,
+    #[serde(rename = "issuetype")]
+    pub issue_type: Option<JiraNamed>,
+    pub priority: Option<JiraNamed>,
+    pub resolution: Option<JiraNamed>,
+    #[serde(rename = "resolutiondate")]
+    pub resolution_date: Option<String>,
+    #[serde(default)]
+    pub labels: Vec<String>,
+    #[serde(flatten)]
+    pub other: HashMap<String, serde_json::Value>,
+}
+
+/// Issue types, priorities and resolutions only matter to us by name.
+#[derive(Deserialize)]
+pub struct JiraNamed {
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+pub struct JiraStatus {
+    pub name: String,
+    #[serde(rename = "statusCategory")]
+    pub category: Option<JiraStatusCategory>,
+}
+
+/// One of the fixed categories `new`, `indeterminate` and `done` every status belongs to.
+#[derive(Deserialize)]
+pub struct JiraStatusCategory {
+    pub key: String,
+}
+
+/// A sprint as returned by the agile API or in the sprint field of Jira Cloud. Dates are
+/// normalized to RFC 3339 in UTC before being stored.
+#[derive(Deserialize, Serialize, Clone)]
+pub struct Sprint {
+    pub id: i64,
+    pub name: String,
+    pub state: String,
+    #[serde(alias = "startDate")]
+    pub start_date: Option<String>,
+    #[serde(alias = "endDate")]
+    pub end_date: Option<String>,
+    #[serde(alias = "completeDate")]
+    pub complete_date: Option<String>,
+    #[serde(alias = "originBoardId", alias = "boardId")]
+    pub board_id: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct SprintListResponse {
+    #[serde(rename = "isLast", default)]
+    pub is_last: bool,
+    pub values: Vec<Sprint>,
 }
 
 #[derive(Deserialize)]
+pub struct IssueWithChangelog {
+    pub fields: IssueCreatedFields,
+    /// Filled in page by page, see `ChangelogPage`.
+    #[serde(default)]
+    pub changelog: Changelog,
+}
+
+#[derive(Deserialize)]
+pub struct IssueCreatedFields {
+    pub created: String,
+    #[serde(default)]
+    pub subtasks: Vec<SubtaskRef>,
+}
+
+#[derive(Deserialize)]
+pub struct SubtaskRef {
+    pub key: String,
+}
+
+#[derive(Deserialize, Default)]
+pub struct Changelog {
+    pub histories: Vec<ChangelogHistory>,
+}
+
+#[derive(Deserialize)]
+pub struct ChangelogHistory {
+    pub created: String,
+    pub items: Vec<ChangelogItem>,
+}
+
+#[derive(Deserialize)]
+pub struct ChangelogItem {
+    pub field: String,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    /// Display value, e.g. the status name where `from` and `to` hold status IDs.
+    #[serde(rename = "fromString")]
+    pub from_text: Option<String>,
+    #[serde(rename = "toString")]
+    pub to_text: Option<String>,
+}
+
+/// A page of `/issue/{key}/changelog`. `expand=changelog` only returns the first page of histories.
+#[derive(Deserialize)]
+pub struct ChangelogPage {
+    #[serde(rename = "startAt")]
+    pub start_at: u64,
+    pub total: u64,
+    #[serde(rename = "isLast", default)]
+    pub is_last: bool,
+    pub values: Vec<ChangelogHistory>,
+}
+
+#[derive(Deserialize)]
+pub struct WorklogListResponse {
+    #[serde(rename = "startAt")]
+    pub start_at: u64,
+    pub total: u64,
+    pub worklogs: Vec<Worklog>,
+}
+
+#[derive(Deserialize)]
+pub struct Worklog {
+    pub started: String,
+    #[serde(rename = "timeSpentSeconds")]
+    pub time_spent_seconds: u64,
+}
+
+#[derive(Deserialize, Serialize)]
 pub struct Epic {
     pub key: String,
     pub summary: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize, Clone)]
 pub struct IssueLog {
     pub key: String,
     pub summary: String,
     pub epic_key: String,
     pub time_estimate: i64,
     pub time_spent: i64,
+    pub remaining_estimate: i64,
+    pub story_points: Option<f64>,
     pub updated_at: String,
     pub assignee: String,
+    pub status: String,
+    /// Key of the status category, see `STATUS_CATEGORY_DONE`.
+    pub status_category: String,
+    pub issue_type: String,
+    pub priority: String,
+    pub resolution: String,
+    pub resolution_date: Option<String>,
+    pub labels: Vec<String>,
+    pub sprint_ids: Vec<i64>,
+}
+
+pub const STATUS_CATEGORY_DONE: &str = "done";
+
+impl IssueLog {
+    pub fn is_done(&self) -> bool {
+        self.status_category == STATUS_CATEGORY_DONE
+    }
+
+    /// Whether both snapshots have the same values, regardless of when they were taken.
+    pub fn same_values(&self, other: &IssueLog) -> bool {
+        self.key == other.key
+            && self.summary == other.summary
+            && self.epic_key == other.epic_key
+            && self.time_estimate == other.time_estimate
+            && self.time_spent == other.time_spent
+            && self.remaining_estimate == other.remaining_estimate
+            && self.story_points == other.story_points
+            && self.assignee == other.assignee
+            && self.status == other.status
+            && self.status_category == other.status_category
+            && self.issue_type == other.issue_type
+            && self.priority == other.priority
+            && self.resolution == other.resolution
+            && self.resolution_date == other.resolution_date
+            && self.labels == other.labels
+            && self.sprint_ids == other.sprint_ids
+    }
+}
+
+/// Why a stored issue was no longer returned for its epic.
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum RemovalReason {
+    /// Jira answers 404, the issue was deleted or we lost access to it.
+    Deleted,
+    /// The issue links to another epic that is not tracked, or to none at all.
+    MovedToOtherEpic,
+    /// The issue is still in the epic but no longer matches the issues JQL.
+    NoLongerMatchesJql,
+}
+
+impl RemovalReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RemovalReason::Deleted => "deleted",
+            RemovalReason::MovedToOtherEpic => "moved_to_other_epic",
+            RemovalReason::NoLongerMatchesJql => "no_longer_matches_jql",
+        }
+    }
+}
+
+impl FromStr for RemovalReason {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "deleted" => Ok(RemovalReason::Deleted),
+            "moved_to_other_epic" => Ok(RemovalReason::MovedToOtherEpic),
+            "no_longer_matches_jql" => Ok(RemovalReason::NoLongerMatchesJql),
+            _ => Err(format!("Unknown removal reason {:?}", value)),
+        }
+    }
+}
+
+/// Recorded when a full sync no longer returns an issue for the epic it was last stored in.
+#[derive(Serialize)]
+pub struct IssueRemoval {
+    pub key: String,
+    pub epic_key: String,
+    pub removed_at: String,
+    pub reason: RemovalReason,
+}
+
+/// One run of the collector. `finished_at` is `None` while it is running.
+#[derive(Serialize)]
+pub struct SyncRun {
+    pub id: i64,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+    /// The epic a manual refresh was limited to, `None` for regular syncs of every epic.
+    pub epic_key: Option<String>,
+    pub full_sync: bool,
+    pub epics_fetched: i64,
+    pub issues_fetched: i64,
+    pub snapshots_written: i64,
+    pub issues_removed: i64,
+    pub error: Option<String>,
 }
@@ -0,0 +1,257 @@
+use crate::config::ForecastConfig;
+use crate::forecast::ForecastStatus;
+use crate::metrics::SECONDS_PER_DAY;
+use crate::models::IssueLog;
+use itertools::Itertools;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+/// Runs still unfinished after this many simulated days are given up on.
+const MAX_SIMULATED_DAYS: i64 = 2 * 365;
+
+const PERCENTILES: [u32; 3] = [50, 85, 95];
+
+#[derive(Serialize)]
+pub struct AssigneeWork {
+    /// Empty for unassigned issues, whose work is simulated with the throughput of everyone.
+    pub assignee: String,
+    pub remaining_estimate: i64,
+    /// Mean seconds logged per day over the history, counting days without any time logged.
+    pub time_spent_per_day: f64,
+    /// Days of the history with any time logged.
+    pub active_days: usize,
+}
+
+#[derive(Serialize)]
+pub struct Percentile {
+    pub percentile: u32,
+    /// Days from now, `None` when the percentile falls on runs that never finished.
+    pub days: Option<i64>,
+    pub completion: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct HistogramBin {
+    pub days: i64,
+    pub runs: usize,
+}
+
+#[derive(Serialize)]
+pub struct MonteCarloForecast {
+    pub epic_key: String,
+    pub status: ForecastStatus,
+    pub simulations: usize,
+    /// Days throughput was sampled from, at most the configured history.
+    pub history_days: i64,
+    pub remaining_estimate: i64,
+    pub assignees: Vec<AssigneeWork>,
+    /// P50, P85 and P95 of the simulated completion dates.
+    pub percentiles: Vec<Percentile>,
+    /// Number of runs finishing after each number of days, without empty bins.
+    pub histogram: Vec<HistogramBin>,
+    /// Runs that did not finish within two years.
+    pub unfinished_runs: usize,
+}
+
+/// Seconds logged per day by each assignee over the last `days` days, oldest first, from how
+/// much the time spent grew between consecutive snapshots of each issue. The growth is credited
+/// to the assignee of the later snapshot.
+fn daily_time_spent_by_assignee(
+    issue_logs: &[IssueLog],
+    days: i64,
+    now: i64,
+) -> Result<BTreeMap<&str, Vec<f64>>, chrono::ParseError> {
+    let mut daily_time_spent = BTreeMap::new();
+    let logs_by_issue = issue_logs
+        .iter()
+        .map(|log| {
+            Ok((
+                chrono::DateTime::parse_from_rfc3339(&log.updated_at)?.timestamp(),
+                log,
+            ))
+        })
+        .collect::<Result<Vec<_>, chrono::ParseError>>()?
+        .into_iter()
+        .into_group_map_by(|(_, log)| log.key.as_str());
+    for logs in logs_by_issue.into_values() {
+        for ((_, previous), (date, log)) in logs
+            .into_iter()
+            .sorted_by_key(|(date, _)| *date)
+            .tuple_windows()
+        {
+            let days_ago = (now - date) / SECONDS_PER_DAY;
+            let time_spent = log.time_spent - previous.time_spent;
+            if time_spent <= 0 || days_ago < 0 || days_ago >= days {
+                continue;
+            }
+            daily_time_spent
+                .entry(log.assignee.as_str())
+                .or_insert_with(|| vec![0.0; days as usize])[(days - 1 - days_ago) as usize] +=
+                time_spent as f64;
+        }
+    }
+    Ok(daily_time_spent)
+}
+
+/// Sum of the remaining estimates of the latest snapshot of each issue still in the epic, per assignee.
+fn remaining_estimate_by_assignee<'a>(
+    epic_key: &str,
+    issue_logs: &'a [IssueLog],
+) -> Result<BTreeMap<&'a str, i64>, chrono::ParseError> {
+    let mut latest_logs: HashMap<&str, (i64, &IssueLog)> = HashMap::new();
+    for log in issue_logs {
+        let date = chrono::DateTime::parse_from_rfc3339(&log.updated_at)?.timestamp();
+        if latest_logs
+            .get(log.key.as_str())
+            .is_none_or(|(latest, _)| date >= *latest)
+        {
+            latest_logs.insert(&log.key, (date, log));
+        }
+    }
+    let mut remaining = BTreeMap::new();
+    for (_, log) in latest_logs.into_values() {
+        if log.epic_key == epic_key && log.remaining_estimate > 0 {
+            *remaining.entry(log.assignee.as_str()).or_insert(0) += log.remaining_estimate;
+        }
+    }
+    Ok(remaining)
+}
+
+/// Days until `remaining` seconds are done when each day's work is drawn from `samples`, `None`
+/// when that takes longer than `MAX_SIMULATED_DAYS`.
+fn simulate_days(samples: &[f64], remaining: f64, rng: &mut StdRng) -> Option<i64> {
+    let mut left = remaining;
+    let mut days = 0;
+    while left > 0.0 {
+        if days == MAX_SIMULATED_DAYS {
+            return None;
+        }
+        left -= samples.choose(rng).copied().unwrap_or(0.0);
+        days += 1;
+    }
+    Some(days)
+}
+
+/// Forecasts when the remaining estimate of the epic is done by simulating each assignee working
+/// off their own remaining estimate, one day at a time, with days of time they logged on the epic
+/// over the history drawn at random. Assignees work in parallel, so a run finishes with its slowest
+/// assignee, which keeps one person holding most of the work from hiding behind a team average.
+pub fn simulate_epic(
+    epic_key: &str,
+    issue_logs: &[IssueLog],
+    config: &ForecastConfig,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<MonteCarloForecast, chrono::ParseError> {
+    let first_snapshot = issue_logs
+        .iter()
+        .map(|log| Ok(chrono::DateTime::parse_from_rfc3339(&log.updated_at)?.timestamp()))
+        .collect::<Result<Vec<_>, chrono::ParseError>>()?
+        .into_iter()
+        .min();
+    let days = first_snapshot
+        .map(|first| ((now.timestamp() - first) / SECONDS_PER_DAY).min(config.history_days))
+        .unwrap_or(0)
+        .max(0);
+    let daily_time_spent = daily_time_spent_by_assignee(issue_logs, days, now.timestamp())?;
+    let everyone = daily_time_spent
+        .values()
+        .flatten()
+        .copied()
+        .collect::<Vec<_>>();
+    let remaining = remaining_estimate_by_assignee(epic_key, issue_logs)?;
+
+    let work = remaining
+        .iter()
+        .map(|(assignee, remaining)| {
+            let samples = if assignee.is_empty() {
+                everyone.as_slice()
+            } else {
+                daily_time_spent
+                    .get(assignee)
+                    .map_or(&[][..], Vec::as_slice)
+            };
+            (*assignee, *remaining, samples)
+        })
+        .collect::<Vec<_>>();
+
+    let mut forecast = MonteCarloForecast {
+        epic_key: epic_key.to_string(),
+        status: ForecastStatus::InsufficientHistory,
+        simulations: config.simulations,
+        history_days: days,
+        remaining_estimate: remaining.values().sum(),
+        assignees: work
+            .iter()
+            .map(|(assignee, remaining, samples)| AssigneeWork {
+                assignee: assignee.to_string(),
+                remaining_estimate: *remaining,
+                time_spent_per_day: if samples.is_empty() {
+                    0.0
+                } else {
+                    samples.iter().fold(0.0, |sum, value| sum + value) / samples.len() as f64
+                },
+                active_days: samples.iter().filter(|value| **value > 0.0).count(),
+            })
+            .collect(),
+        percentiles: Vec::new(),
+        histogram: Vec::new(),
+        unfinished_runs: 0,
+    };
+    if first_snapshot.is_some() && work.is_empty() {
+        forecast.status = ForecastStatus::Done;
+        return Ok(forecast);
+    }
+    if days == 0 {
+        return Ok(forecast);
+    }
+    // Someone who logged nothing over the history never finishes, no need to simulate that.
+    if forecast
+        .assignees
+        .iter()
+        .any(|assignee| assignee.active_days == 0)
+    {
+        forecast.status = ForecastStatus::NoProgress;
+        forecast.unfinished_runs = config.simulations;
+        return Ok(forecast);
+    }
+
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let mut outcomes = (0..config.simulations)
+        .map(|_| {
+            work.iter().try_fold(0, |slowest, (_, remaining, samples)| {
+                Some(simulate_days(samples, *remaining as f64, &mut rng)?.max(slowest))
+            })
+        })
+        .collect::<Vec<_>>();
+    // Unfinished runs sort last.
+    outcomes.sort_by_key(|days| days.unwrap_or(i64::MAX));
+
+    forecast.status = ForecastStatus::Projected;
+    forecast.unfinished_runs = outcomes.iter().filter(|days| days.is_none()).count();
+    forecast.percentiles = PERCENTILES
+        .iter()
+        .map(|percentile| {
+            let index = (*percentile as usize * outcomes.len()).div_ceil(100).max(1) - 1;
+            let days = outcomes[index];
+            Percentile {
+                percentile: *percentile,
+                days,
+                completion: days.map(|days| (now + chrono::Duration::days(days)).to_rfc3339()),
+            }
+        })
+        .collect();
+    forecast.histogram = outcomes
+        .iter()
+        .flatten()
+        .chunk_by(|days| **days)
+        .into_iter()
+        .map(|(days, runs)| HistogramBin {
+            days,
+            runs: runs.count(),
+        })
+        .collect();
+    Ok(forecast)
+}
@@ -1,5 +1,3 @@
-use svg;
-
 pub struct Point {
     pub x: f64,
     pub y: f64,
@@ -7,7 +5,24 @@ pub struct Point {
 
 pub struct GraphStyle {
     pub stroke_color: &'static str,
-    pub fill_color: &'static str,
+    /// Color of the area below the line, `None` to leave it empty.
+    pub fill_color: Option<&'static str>,
+    /// Dash pattern of the line, e.g. `6 4`, `None` for a solid line.
+    pub dash_array: Option<&'static str>,
+}
+
+/// A bar rising from the bottom of the graph to `y`.
+pub struct Bar {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+}
+
+/// A labelled vertical line across the whole height of the graph.
+pub struct Marker {
+    pub x: f64,
+    pub label: String,
+    pub color: &'static str,
 }
 
 pub struct Renderer {
@@ -27,19 +42,25 @@ impl Renderer {
         }
     }
 
-    pub fn render_line_graphs(&mut self, graphs: Vec<(Vec<Point>, GraphStyle)>) -> String {
-        let mut document = svg::Document::new()
+    fn new_document(&self) -> svg::Document {
+        svg::Document::new()
             .set(
                 "viewBox",
                 (-self.margin, -self.margin, self.width + 2 * self.margin, self.height + 2 * self.margin),
             )
             .set("width", self.width + 2 * self.margin)
-            .set("height", self.height + 2 * self.margin);
+            .set("height", self.height + 2 * self.margin)
+    }
+
+    pub fn render_line_graphs(&mut self, graphs: Vec<(Vec<Point>, GraphStyle)>) -> String {
+        let mut document = self.new_document();
 
         for (points, style) in graphs {
             if !points.is_empty() {
-                document = document.add(self.get_filled_area(&points, style.fill_color));
-                document = document.add(self.get_path(&points, style.stroke_color));
+                if let Some(fill_color) = style.fill_color {
+                    document = document.add(self.get_filled_area(&points, fill_color));
+                }
+                document = document.add(self.get_path(&points, &style));
             }
         }
 
@@ -47,7 +68,49 @@ impl Renderer {
         self.document.to_string()
     }
 
-    fn get_path(&self, points: &[Point], color: &str) -> svg::node::element::Path {
+    /// Bars in `color`, with the markers drawn on top and their labels stacked from the top left.
+    pub fn render_histogram(&mut self, bars: Vec<Bar>, color: &str, markers: Vec<Marker>) -> String {
+        let mut document = self.new_document();
+
+        for bar in bars {
+            document = document.add(
+                svg::node::element::Rectangle::new()
+                    .set("x", bar.x)
+                    .set("y", bar.y)
+                    .set("width", bar.width)
+                    .set("height", self.height as f64 - bar.y)
+                    .set("fill", color)
+                    .set("fill-opacity", "0.5")
+                    .set("stroke", color),
+            );
+        }
+        for (index, marker) in markers.into_iter().enumerate() {
+            document = document
+                .add(
+                    svg::node::element::Line::new()
+                        .set("x1", marker.x)
+                        .set("y1", 0)
+                        .set("x2", marker.x)
+                        .set("y2", self.height)
+                        .set("stroke", marker.color)
+                        .set("stroke-width", "2")
+                        .set("stroke-dasharray", "6 4"),
+                )
+                .add(
+                    svg::node::element::Text::new(marker.label)
+                        .set("x", marker.x + 4.0)
+                        .set("y", 12 + 14 * index)
+                        .set("font-family", "sans-serif")
+                        .set("font-size", "12")
+                        .set("fill", marker.color),
+                );
+        }
+
+        self.document = document;
+        self.document.to_string()
+    }
+
+    fn get_path(&self, points: &[Point], style: &GraphStyle) -> svg::node::element::Path {
         let mut path_data = svg::node::element::path::Data::new();
         if let Some(first) = points.first() {
             path_data = path_data.move_to((first.x, first.y));
@@ -55,11 +118,15 @@ impl Renderer {
                 path_data = path_data.line_to((point.x, point.y));
             }
         }
-        svg::node::element::Path::new()
+        let path = svg::node::element::Path::new()
             .set("d", path_data)
-            .set("stroke", color)
+            .set("stroke", style.stroke_color)
             .set("stroke-width", "2")
-            .set("fill", "none")
+            .set("fill", "none");
+        match style.dash_array {
+            Some(dash_array) => path.set("stroke-dasharray", dash_array),
+            None => path,
+        }
     }
 
     fn get_filled_area(&self, points: &[Point], color: &str) -> svg::node::element::Path {
@@ -0,0 +1,73 @@
+use crate::database::{Database, DatabaseError};
+use crate::health::{evaluate_epic, latest_logs_of_epic, EpicHealth, HealthRules};
+use crate::metrics::EpicMetrics;
+use crate::models::Epic;
+use serde::Serialize;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ReportError {
+    #[error("Database error: {0}")]
+    Database(#[from] DatabaseError),
+    #[error("Invalid timestamp in stored data: {0}")]
+    InvalidTimestamp(#[from] chrono::ParseError),
+}
+
+#[derive(Serialize)]
+pub struct EpicReport {
+    pub epic: Epic,
+    pub metrics: EpicMetrics,
+    pub health: EpicHealth,
+}
+
+/// Builds the metrics and health of every stored epic.
+pub async fn build_report(
+    database: &Database,
+    rules: &HealthRules,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<Vec<EpicReport>, ReportError> {
+    let mut report = Vec::new();
+    for epic in database.get_epics().await? {
+        let epic_logs = database.get_logs_of_epic(&epic.key).await?;
+        let metrics = EpicMetrics::from_latest_logs(&epic.key, &latest_logs_of_epic(&epic.key, &epic_logs));
+        let health = evaluate_epic(&epic.key, &epic_logs, rules, now)?;
+        report.push(EpicReport { epic, metrics, health });
+    }
+    Ok(report)
+}
+
+pub fn format_hours(seconds: i64) -> String {
+    format!("{:.1}h", seconds as f64 / 3600.0)
+}
+
+pub fn to_text(report: &[EpicReport]) -> String {
+    let mut text = String::new();
+    for EpicReport { epic, metrics, health } in report {
+        text.push_str(&format!(
+            "{} {} [{}]\n",
+            epic.key,
+            epic.summary,
+            health.status.as_str()
+        ));
+        text.push_str(&format!(
+            "  {} issues ({} done, {} unestimated), estimate {}, spent {}, remaining {}",
+            metrics.issue_count,
+            metrics.done_issue_count,
+            metrics.unestimated_issue_count,
+            format_hours(metrics.time_estimate),
+            format_hours(metrics.time_spent),
+            format_hours(metrics.time_remaining),
+        ));
+        if let Some(percent_spent) = metrics.percent_spent {
+            text.push_str(&format!(" ({:.0}% spent)", percent_spent));
+        }
+        if metrics.story_points > 0.0 {
+            text.push_str(&format!(", {} story points", metrics.story_points));
+        }
+        text.push('\n');
+        for finding in &health.findings {
+            text.push_str(&format!("  - {}: {}\n", finding.status.as_str(), finding.reason));
+        }
+    }
+    text
+}
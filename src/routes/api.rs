@@ -0,0 +1,313 @@
+use crate::diagnostics::{run_diagnostics, Diagnostics};
+use crate::forecast::{forecast_epic, Forecast};
+use crate::health::{evaluate_epic, EpicHealth};
+use crate::metrics::EpicMetrics;
+use crate::models::{Epic, IssueLog, IssueRemoval, SyncRun};
+use crate::monte_carlo::{simulate_epic, MonteCarloForecast};
+use crate::routes::sync::{sync_now, SyncQuery};
+use crate::routes::AppError;
+use crate::sprints::{epic_sprint_progress, SprintProgress};
+use crate::webhook::{handle_webhook, verify, WebhookOutcome};
+use crate::AppState;
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::Json;
+use serde::{Deserialize, Serialize};
+
+const DEFAULT_PER_PAGE: usize = 50;
+const MAX_PER_PAGE: usize = 500;
+
+#[derive(Deserialize)]
+pub struct PageQuery {
+    page: Option<usize>,
+    per_page: Option<usize>,
+}
+
+#[derive(Deserialize)]
+pub struct IssueQuery {
+    epic_key: Option<String>,
+    assignee: Option<String>,
+    page: Option<usize>,
+    per_page: Option<usize>,
+}
+
+impl IssueQuery {
+    fn page_query(&self) -> PageQuery {
+        PageQuery {
+            page: self.page,
+            per_page: self.per_page,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct WebhookQuery {
+    secret: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct Page<T> {
+    items: Vec<T>,
+    page: usize,
+    per_page: usize,
+    total: usize,
+}
+
+impl<T> Page<T> {
+    fn paginate(items: Vec<T>, query: &PageQuery) -> Self {
+        let page = query.page.unwrap_or(1).max(1);
+        let per_page = query.per_page.unwrap_or(DEFAULT_PER_PAGE).clamp(1, MAX_PER_PAGE);
+        let total = items.len();
+
+        Self {
+            items: items
+                .into_iter()
+                .skip((page - 1) * per_page)
+                .take(per_page)
+                .collect(),
+            page,
+            per_page,
+            total,
+        }
+    }
+}
+
+pub fn router() -> axum::Router<AppState> {
+    axum::Router::new()
+        .route("/epics", axum::routing::get(epics))
+        .route("/epics/{epic_key}", axum::routing::get(epic))
+        .route("/epics/{epic_key}/issues", axum::routing::get(epic_issues))
+        .route("/epics/{epic_key}/metrics", axum::routing::get(epic_metrics))
+        .route("/epics/{epic_key}/health", axum::routing::get(epic_health))
+        .route("/epics/{epic_key}/sprints", axum::routing::get(epic_sprints))
+        .route("/epics/{epic_key}/forecast", axum::routing::get(epic_forecast))
+        .route("/epics/{epic_key}/monte-carlo", axum::routing::get(epic_monte_carlo))
+        .route("/health", axum::routing::get(health))
+        .route("/diagnostics", axum::routing::get(diagnostics))
+        .route("/issues", axum::routing::get(issues))
+        .route("/issues/{issue_key}/history", axum::routing::get(issue_history))
+        .route("/removals", axum::routing::get(removals))
+        .route("/sync-runs", axum::routing::get(sync_runs))
+        .route("/sync", axum::routing::post(sync))
+        .route("/webhooks/jira", axum::routing::post(jira_webhook))
+}
+
+async fn find_epic(state: &AppState, epic_key: &str) -> Result<Epic, AppError> {
+    state
+        .database
+        .get_epics()
+        .await?
+        .into_iter()
+        .find(|epic| epic.key == epic_key)
+        .ok_or_else(|| AppError::EpicNotFound(epic_key.to_string()))
+}
+
+async fn latest_issue_logs(
+    state: &AppState,
+    epic_key: Option<&str>,
+    assignee: Option<&str>,
+) -> Result<Vec<IssueLog>, AppError> {
+    let mut issue_logs = state
+        .database
+        .get_all_latest_issue_logs()
+        .await?
+        .into_iter()
+        .filter(|log| epic_key.is_none_or(|epic_key| log.epic_key == epic_key))
+        .filter(|log| assignee.is_none_or(|assignee| log.assignee == assignee))
+        .collect::<Vec<_>>();
+    issue_logs.sort_by(|a, b| a.key.cmp(&b.key));
+    Ok(issue_logs)
+}
+
+async fn epics(
+    State(state): State<AppState>,
+    Query(query): Query<PageQuery>,
+) -> Result<Json<Page<Epic>>, AppError> {
+    let epics = state.database.get_epics().await?;
+    Ok(Json(Page::paginate(epics, &query)))
+}
+
+async fn epic(
+    State(state): State<AppState>,
+    Path(epic_key): Path<String>,
+) -> Result<Json<Epic>, AppError> {
+    Ok(Json(find_epic(&state, &epic_key).await?))
+}
+
+async fn epic_issues(
+    State(state): State<AppState>,
+    Path(epic_key): Path<String>,
+    Query(query): Query<IssueQuery>,
+) -> Result<Json<Page<IssueLog>>, AppError> {
+    find_epic(&state, &epic_key).await?;
+    let issue_logs = latest_issue_logs(&state, Some(&epic_key), query.assignee.as_deref()).await?;
+    Ok(Json(Page::paginate(issue_logs, &query.page_query())))
+}
+
+async fn epic_metrics(
+    State(state): State<AppState>,
+    Path(epic_key): Path<String>,
+) -> Result<Json<EpicMetrics>, AppError> {
+    find_epic(&state, &epic_key).await?;
+    let issue_logs = latest_issue_logs(&state, Some(&epic_key), None).await?;
+    let issue_logs = issue_logs.iter().collect::<Vec<_>>();
+    Ok(Json(EpicMetrics::from_latest_logs(&epic_key, &issue_logs)))
+}
+
+async fn epic_sprints(
+    State(state): State<AppState>,
+    Path(epic_key): Path<String>,
+) -> Result<Json<Vec<SprintProgress>>, AppError> {
+    find_epic(&state, &epic_key).await?;
+    let issue_logs = state.database.get_logs_of_epic(&epic_key).await?;
+    let sprints = state.database.get_sprints().await?;
+    Ok(Json(epic_sprint_progress(
+        &epic_key,
+        &issue_logs,
+        sprints,
+        chrono::Utc::now(),
+    )?))
+}
+
+async fn epic_forecast(
+    State(state): State<AppState>,
+    Path(epic_key): Path<String>,
+) -> Result<Json<Forecast>, AppError> {
+    find_epic(&state, &epic_key).await?;
+    let issue_logs = state.database.get_logs_of_epic(&epic_key).await?;
+    Ok(Json(forecast_epic(
+        &epic_key,
+        &issue_logs,
+        state.config.forecast.window_days,
+        chrono::Utc::now(),
+    )?))
+}
+
+async fn epic_monte_carlo(
+    State(state): State<AppState>,
+    Path(epic_key): Path<String>,
+) -> Result<Json<MonteCarloForecast>, AppError> {
+    find_epic(&state, &epic_key).await?;
+    let issue_logs = state.database.get_logs_of_epic(&epic_key).await?;
+    Ok(Json(simulate_epic(
+        &epic_key,
+        &issue_logs,
+        &state.config.forecast,
+        chrono::Utc::now(),
+    )?))
+}
+
+async fn get_epic_health(state: &AppState, epic_key: &str) -> Result<EpicHealth, AppError> {
+    let epic_logs = state.database.get_logs_of_epic(epic_key).await?;
+    Ok(evaluate_epic(
+        epic_key,
+        &epic_logs,
+        &state.config.health,
+        chrono::Utc::now(),
+    )?)
+}
+
+async fn epic_health(
+    State(state): State<AppState>,
+    Path(epic_key): Path<String>,
+) -> Result<Json<EpicHealth>, AppError> {
+    find_epic(&state, &epic_key).await?;
+    Ok(Json(get_epic_health(&state, &epic_key).await?))
+}
+
+async fn health(State(state): State<AppState>) -> Result<Json<Vec<EpicHealth>>, AppError> {
+    let epics = state.database.get_epics().await?;
+    let mut health = Vec::new();
+    for epic in epics {
+        health.push(get_epic_health(&state, &epic.key).await?);
+    }
+    Ok(Json(health))
+}
+
+async fn diagnostics(State(state): State<AppState>) -> (StatusCode, Json<Diagnostics>) {
+    let diagnostics = run_diagnostics(&state.jira_client, &state.database, &state.config).await;
+    let status = if diagnostics.ok {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(diagnostics))
+}
+
+async fn issues(
+    State(state): State<AppState>,
+    Query(query): Query<IssueQuery>,
+) -> Result<Json<Page<IssueLog>>, AppError> {
+    let issue_logs = latest_issue_logs(
+        &state,
+        query.epic_key.as_deref(),
+        query.assignee.as_deref(),
+    )
+    .await?;
+    Ok(Json(Page::paginate(issue_logs, &query.page_query())))
+}
+
+async fn issue_history(
+    State(state): State<AppState>,
+    Path(issue_key): Path<String>,
+    Query(query): Query<PageQuery>,
+) -> Result<Json<Page<IssueLog>>, AppError> {
+    let mut issue_logs = state.database.get_logs_of_issue(&issue_key).await?;
+    if issue_logs.is_empty() {
+        return Err(AppError::IssueNotFound(issue_key));
+    }
+    issue_logs.sort_by(|a, b| a.updated_at.cmp(&b.updated_at));
+    Ok(Json(Page::paginate(issue_logs, &query)))
+}
+
+async fn removals(
+    State(state): State<AppState>,
+    Query(query): Query<PageQuery>,
+) -> Result<Json<Page<IssueRemoval>>, AppError> {
+    let removals = state.database.get_issue_removals().await?;
+    Ok(Json(Page::paginate(removals, &query)))
+}
+
+async fn sync_runs(
+    State(state): State<AppState>,
+    Query(query): Query<PageQuery>,
+) -> Result<Json<Page<SyncRun>>, AppError> {
+    let runs = state.database.get_sync_runs(None).await?;
+    Ok(Json(Page::paginate(runs, &query)))
+}
+
+async fn sync(
+    State(state): State<AppState>,
+    Query(query): Query<SyncQuery>,
+) -> Result<Json<SyncRun>, AppError> {
+    Ok(Json(sync_now(&state, query.epic_key.as_deref()).await?))
+}
+
+async fn jira_webhook(
+    State(state): State<AppState>,
+    Query(query): Query<WebhookQuery>,
+    headers: HeaderMap,
+    body: axum::body::Bytes,
+) -> Result<Json<WebhookOutcome>, AppError> {
+    let secret = &state.config.webhook.secret;
+    if secret.is_empty() {
+        return Err(AppError::WebhooksDisabled);
+    }
+    let signature = headers
+        .get("X-Hub-Signature")
+        .and_then(|signature| signature.to_str().ok());
+    if !verify(secret, signature, query.secret.as_deref(), &body) {
+        return Err(AppError::InvalidWebhookSecret);
+    }
+    // Without the collector the data must not change. A sync can take longer than Jira waits for
+    // a webhook to be answered, so instead of waiting for it Jira is asked to deliver again.
+    let Some(sync_lock) = &state.sync_lock else {
+        return Err(AppError::SyncDisabled);
+    };
+    let Ok(_sync_guard) = sync_lock.try_lock() else {
+        return Err(AppError::WebhookDeferred);
+    };
+    Ok(Json(
+        handle_webhook(&state.jira_client, &state.database, &state.config, &body).await?,
+    ))
+}
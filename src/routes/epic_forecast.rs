@@ -0,0 +1,34 @@
+use crate::forecast_graph::render_forecast_histogram;
+use crate::monte_carlo::simulate_epic;
+use crate::routes::AppError;
+use crate::AppState;
+
+/// Histogram of the Monte Carlo forecast of the epic, swapped into the root page.
+pub async fn epic_forecast_svg(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    axum::extract::Path(epic_key): axum::extract::Path<String>,
+) -> Result<impl axum::response::IntoResponse, AppError> {
+    let issue_logs = state.database.get_logs_of_epic(&epic_key).await?;
+    if issue_logs.is_empty()
+        && !state
+            .database
+            .get_epics()
+            .await?
+            .iter()
+            .any(|e| e.key == epic_key)
+    {
+        return Err(AppError::EpicNotFound(epic_key));
+    }
+    let forecast = simulate_epic(
+        &epic_key,
+        &issue_logs,
+        &state.config.forecast,
+        chrono::Utc::now(),
+    )?;
+    let svg_content = render_forecast_histogram(&forecast, &state.config.graphs.epic)?;
+
+    Ok((
+        [(axum::http::header::CONTENT_TYPE, "image/svg+xml")],
+        svg_content,
+    ))
+}
@@ -0,0 +1,33 @@
+use crate::forecast::forecast_epic;
+use crate::routes::AppError;
+use crate::time_graph::{render_epic_time_graph, GraphQuery};
+use crate::AppState;
+
+pub async fn epic_svg(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    axum::extract::Path(epic_key): axum::extract::Path<String>,
+    axum::extract::Query(query): axum::extract::Query<GraphQuery>,
+) -> Result<impl axum::response::IntoResponse, AppError> {
+    let metrics = query
+        .metrics(state.config.graphs.metrics)
+        .map_err(AppError::InvalidQuery)?;
+    let issue_logs = state.database.get_logs_of_epic(&epic_key).await?;
+    if issue_logs.is_empty() && !state.database.get_epics().await?.iter().any(|e| e.key == epic_key) {
+        return Err(AppError::EpicNotFound(epic_key));
+    }
+    let last_seen = state.database.get_epic_last_seen(&epic_key).await?;
+    let forecast = forecast_epic(&epic_key, &issue_logs, state.config.forecast.window_days, chrono::Utc::now())?;
+    let svg_content = render_epic_time_graph(
+        &epic_key,
+        issue_logs,
+        last_seen,
+        &state.config.graphs.epic,
+        metrics,
+        Some(&forecast),
+    )?;
+
+    Ok((
+        [(axum::http::header::CONTENT_TYPE, "image/svg+xml")],
+        svg_content,
+    ))
+}
@@ -0,0 +1,58 @@
+use crate::report::format_hours;
+use crate::routes::error::escape_html;
+use crate::routes::AppError;
+use crate::sprints::{epic_sprint_progress, SprintProgress};
+use crate::AppState;
+
+fn render_sprint_row(progress: &SprintProgress) -> String {
+    let story_points = |points: f64| {
+        if points > 0.0 {
+            format!(", {} SP", points)
+        } else {
+            String::new()
+        }
+    };
+    format!(
+        "<tr><td>{}</td><td>{}</td><td>{} issues, {}{}</td><td>{} issues, {}{}</td><td>{}</td></tr>",
+        escape_html(&progress.sprint.name),
+        escape_html(&progress.sprint.state),
+        progress.committed_issues,
+        format_hours(progress.committed_estimate),
+        story_points(progress.committed_story_points),
+        progress.completed_issues,
+        format_hours(progress.completed_estimate),
+        story_points(progress.completed_story_points),
+        format_hours(progress.time_spent),
+    )
+}
+
+/// Table of the epic's committed and completed work per sprint, swapped into the root page.
+pub async fn epic_sprints(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    axum::extract::Path(epic_key): axum::extract::Path<String>,
+) -> Result<axum::response::Html<String>, AppError> {
+    let issue_logs = state.database.get_logs_of_epic(&epic_key).await?;
+    if issue_logs.is_empty()
+        && !state
+            .database
+            .get_epics()
+            .await?
+            .iter()
+            .any(|e| e.key == epic_key)
+    {
+        return Err(AppError::EpicNotFound(epic_key));
+    }
+    let sprints = state.database.get_sprints().await?;
+    let progress = epic_sprint_progress(&epic_key, &issue_logs, sprints, chrono::Utc::now())?;
+
+    if progress.is_empty() {
+        return Ok(axum::response::Html("<p>No sprints</p>".to_string()));
+    }
+    Ok(axum::response::Html(format!(
+        "<table>
+            <tr><th>Sprint</th><th>State</th><th>Committed</th><th>Completed</th><th>Time spent</th></tr>
+            {}
+        </table>",
+        progress.iter().map(render_sprint_row).collect::<Vec<_>>().join("")
+    )))
+}
@@ -0,0 +1,132 @@
+use crate::database::DatabaseError;
+use crate::webhook::WebhookError;
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum AppError {
+    #[error("Database error: {0}")]
+    Database(#[from] DatabaseError),
+    #[error("Invalid timestamp in stored data: {0}")]
+    InvalidTimestamp(#[from] chrono::ParseError),
+    #[error("Issue {0} not found")]
+    IssueNotFound(String),
+    #[error("Epic {0} not found")]
+    EpicNotFound(String),
+    #[error("Invalid query: {0}")]
+    InvalidQuery(String),
+    #[error("A sync is already running")]
+    SyncRunning,
+    #[error("Syncing is disabled because the collector is not running")]
+    SyncDisabled,
+    #[error("Sync failed: {0}")]
+    SyncFailed(String),
+    #[error("Webhooks are disabled because no webhook secret is configured")]
+    WebhooksDisabled,
+    #[error("Missing or invalid webhook secret")]
+    InvalidWebhookSecret,
+    #[error("A sync is running, the webhook has to be delivered again")]
+    WebhookDeferred,
+    #[error("{0}")]
+    Webhook(#[from] WebhookError),
+}
+
+impl AppError {
+    fn status(&self) -> StatusCode {
+        match self {
+            AppError::Database(_) | AppError::InvalidTimestamp(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::IssueNotFound(_) | AppError::EpicNotFound(_) => StatusCode::NOT_FOUND,
+            AppError::InvalidQuery(_) => StatusCode::BAD_REQUEST,
+            AppError::SyncRunning => StatusCode::CONFLICT,
+            AppError::SyncDisabled => StatusCode::FORBIDDEN,
+            AppError::SyncFailed(_) => StatusCode::BAD_GATEWAY,
+            AppError::WebhooksDisabled => StatusCode::NOT_FOUND,
+            AppError::InvalidWebhookSecret => StatusCode::UNAUTHORIZED,
+            // Jira delivers webhooks again that failed with a server error.
+            AppError::WebhookDeferred => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::Webhook(WebhookError::InvalidPayload(_)) => StatusCode::BAD_REQUEST,
+            AppError::Webhook(WebhookError::Jira(_)) => StatusCode::BAD_GATEWAY,
+            AppError::Webhook(WebhookError::Database(_)) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+/// Carries the error message to `render_errors`, which knows the request's `Accept` header.
+#[derive(Clone)]
+struct ErrorMessage(String);
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        // Failed syncs are recorded anyway and their error is about Jira, deferred webhooks only
+        // ask Jira to deliver again, so both are safe to show.
+        let message = if status.is_server_error()
+            && !matches!(self, AppError::SyncFailed(_) | AppError::WebhookDeferred)
+        {
+            println!("Error handling request: {}", self);
+            "Internal server error".to_string()
+        } else {
+            self.to_string()
+        };
+
+        let mut response = (status, message.clone()).into_response();
+        response.extensions_mut().insert(ErrorMessage(message));
+        response
+    }
+}
+
+pub(crate) fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn wants_json(request: &axum::extract::Request) -> bool {
+    request
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains("application/json"))
+}
+
+/// Renders `AppError` responses as JSON or HTML depending on what the client accepts.
+pub async fn render_errors(request: axum::extract::Request, next: axum::middleware::Next) -> Response {
+    let json = wants_json(&request);
+    let response = next.run(request).await;
+
+    let Some(ErrorMessage(message)) = response.extensions().get::<ErrorMessage>().cloned() else {
+        return response;
+    };
+    let status = response.status();
+
+    if json {
+        (
+            status,
+            axum::Json(serde_json::json!({
+                "status": status.as_u16(),
+                "error": message,
+            })),
+        )
+            .into_response()
+    } else {
+        (
+            status,
+            axum::response::Html(format!(
+                r#"
+        <!DOCTYPE html>
+        <html>
+            <body>
+                <h1>{}</h1>
+                <p>{}</p>
+            </body>
+        </html>
+        "#,
+                status,
+                escape_html(&message)
+            )),
+        )
+            .into_response()
+    }
+}
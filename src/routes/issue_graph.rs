@@ -1,19 +1,24 @@
+use crate::routes::AppError;
+use crate::time_graph::{render_issue_time_graph, GraphQuery};
 use crate::AppState;
-use crate::time_graph::render_issue_time_graph;
 
 pub async fn issue_svg(
     axum::extract::State(state): axum::extract::State<AppState>,
     axum::extract::Path(issue_key): axum::extract::Path<String>,
-) -> impl axum::response::IntoResponse {
-    let issue_log = state
-        .database
-        .get_logs_of_issue(&issue_key)
-        .await
-        .expect("Could not get issue log");
-    let svg_content = render_issue_time_graph(issue_log).await;
-    
-    (
+    axum::extract::Query(query): axum::extract::Query<GraphQuery>,
+) -> Result<impl axum::response::IntoResponse, AppError> {
+    let metrics = query
+        .metrics(state.config.graphs.metrics)
+        .map_err(AppError::InvalidQuery)?;
+    let issue_log = state.database.get_logs_of_issue(&issue_key).await?;
+    if issue_log.is_empty() {
+        return Err(AppError::IssueNotFound(issue_key));
+    }
+    let last_seen = state.database.get_issue_last_seen(&issue_key).await?;
+    let svg_content = render_issue_time_graph(issue_log, last_seen, &state.config.graphs.issue, metrics)?;
+
+    Ok((
         [(axum::http::header::CONTENT_TYPE, "image/svg+xml")],
         svg_content,
-    )
-} 
\ No newline at end of file
+    ))
+}
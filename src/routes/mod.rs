@@ -1,5 +1,17 @@
+mod api;
 mod root;
 mod issue_graph;
+mod epic_graph;
+mod epic_forecast;
+mod epic_sprints;
+mod sync;
+mod error;
 
+pub use api::router as api_router;
 pub use root::root;
-pub use issue_graph::issue_svg; 
\ No newline at end of file
+pub use issue_graph::issue_svg;
+pub use epic_graph::epic_svg;
+pub use epic_forecast::epic_forecast_svg;
+pub use epic_sprints::epic_sprints;
+pub use sync::sync_button;
+pub use error::{render_errors, AppError};
\ No newline at end of file
@@ -1,41 +1,221 @@
+use crate::forecast::{forecast_epic, Forecast, ForecastStatus};
+use crate::health::{evaluate_epic, EpicHealth, HealthStatus};
+use crate::models::{IssueLog, SyncRun};
+use crate::routes::error::escape_html;
+use crate::routes::AppError;
 use crate::AppState;
 use itertools::Itertools;
+use std::collections::HashMap;
+
+fn render_health(health: &EpicHealth) -> String {
+    let color = match health.status {
+        HealthStatus::Green => "#27AE60",
+        HealthStatus::Amber => "#F39C12",
+        HealthStatus::Red => "#E74C3C",
+    };
+    format!(
+        "<p>Health: <strong style=\"color: {}\">{}</strong></p><ul>{}</ul>",
+        color,
+        health.status.as_str(),
+        health
+            .findings
+            .iter()
+            .map(|finding| format!("<li>{}</li>", finding.reason))
+            .collect::<Vec<_>>()
+            .join("")
+    )
+}
+
+fn format_date(timestamp: &str) -> String {
+    chrono::DateTime::parse_from_rfc3339(timestamp)
+        .map(|date| date.format("%Y-%m-%d").to_string())
+        .unwrap_or_else(|_| timestamp.to_string())
+}
+
+fn render_forecast(forecast: &Forecast) -> String {
+    let hours_per_day = |seconds: f64| format!("{:.1}h/day", seconds / 3600.0);
+    let projection = match (
+        forecast.status,
+        forecast.expected_completion.as_deref(),
+        forecast.earliest_completion.as_deref(),
+    ) {
+        (ForecastStatus::Done, _, _) => "no remaining estimate left".to_string(),
+        (ForecastStatus::InsufficientHistory, _, _) => "not enough history yet".to_string(),
+        (ForecastStatus::NoProgress, _, _) => format!(
+            "<span style=\"color: #E74C3C\">remaining estimate did not go down in the last {} days</span>",
+            forecast.window_days
+        ),
+        (ForecastStatus::Projected, Some(expected), Some(earliest)) => format!(
+            "done around <strong>{}</strong> ({} to {}), remaining estimate going down {}",
+            format_date(expected),
+            format_date(earliest),
+            forecast
+                .latest_completion
+                .as_deref()
+                .map(format_date)
+                .unwrap_or_else(|| "open-ended".to_string()),
+            hours_per_day(forecast.remaining_burn_per_day)
+        ),
+        (ForecastStatus::Projected, _, _) => String::new(),
+    };
+    format!(
+        "<p>Forecast: {} · {} logged over the last {} days</p>",
+        projection,
+        hours_per_day(forecast.time_spent_per_day),
+        forecast.window_days
+    )
+}
+
+fn render_issue_details(issue: &IssueLog) -> String {
+    let status = match (issue.status.as_str(), issue.resolution.as_str()) {
+        ("", _) => String::new(),
+        (status, "") => escape_html(status),
+        (status, resolution) => format!("{} ({})", escape_html(status), escape_html(resolution)),
+    };
+    let status = if issue.is_done() {
+        format!("<span style=\"color: #27AE60\">{}</span>", status)
+    } else {
+        status
+    };
+    let labels = issue.labels.iter().map(|label| escape_html(label)).join(", ");
+    [escape_html(&issue.issue_type), status, escape_html(&issue.priority), labels]
+        .into_iter()
+        .filter(|detail| !detail.is_empty())
+        .join(" · ")
+}
+
+fn format_age(seconds: i64) -> String {
+    let (count, unit) = match seconds {
+        ..60 => return "less than a minute".to_string(),
+        60..3600 => (seconds / 60, "minute"),
+        3600..86400 => (seconds / 3600, "hour"),
+        _ => (seconds / 86400, "day"),
+    };
+    format!("{} {}{}", count, unit, if count == 1 { "" } else { "s" })
+}
+
+fn seconds_since(timestamp: &str, now: chrono::DateTime<chrono::Utc>) -> Result<i64, chrono::ParseError> {
+    Ok((now - chrono::DateTime::parse_from_rfc3339(timestamp)?.with_timezone(&chrono::Utc)).num_seconds())
+}
+
+/// Banner telling how fresh the data is. The data counts as stale once two collector intervals
+/// passed without a successful sync.
+fn render_sync_status(
+    latest_run: Option<&SyncRun>,
+    last_successful_run: Option<&SyncRun>,
+    interval_secs: u64,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<String, chrono::ParseError> {
+    let mut status = match last_successful_run.and_then(|run| run.finished_at.as_deref()) {
+        Some(finished_at) => {
+            let age = seconds_since(finished_at, now)?;
+            let color = if age > 2 * interval_secs as i64 { "#E74C3C" } else { "#27AE60" };
+            format!("<strong style=\"color: {}\">Last synced {} ago</strong>", color, format_age(age))
+        }
+        None => "<strong style=\"color: #E74C3C\">Never synced</strong>".to_string(),
+    };
+    match latest_run {
+        Some(run) if run.finished_at.is_none() => status.push_str(" · sync running"),
+        Some(SyncRun {
+            finished_at: Some(finished_at),
+            error: Some(error),
+            ..
+        }) => status.push_str(&format!(
+            " · <span style=\"color: #E74C3C\">last sync failed {} ago: {}</span>",
+            format_age(seconds_since(finished_at, now)?),
+            escape_html(error)
+        )),
+        _ => {}
+    }
+    Ok(format!("<p>{}</p>", status))
+}
+
+/// Button triggering an immediate sync, of a single epic if given. Hidden when syncing is disabled.
+fn render_sync_button(state: &AppState, epic_key: Option<&str>) -> String {
+    if state.sync_lock.is_none() {
+        return String::new();
+    }
+    let (url, label) = match epic_key {
+        Some(epic_key) => (format!("/sync?epic_key={}", epic_key), "Refresh Epic"),
+        None => ("/sync".to_string(), "Sync Now"),
+    };
+    format!(
+        "<button hx-post=\"{}\" hx-target=\"this\" hx-swap=\"outerHTML\" hx-disabled-elt=\"this\">{}</button>",
+        url, label
+    )
+}
 
 pub async fn root(
     axum::extract::State(state): axum::extract::State<AppState>,
-) -> axum::response::Html<String> {
-    let epics = state
-        .database
-        .get_epics()
-        .await
-        .expect("Could not get epics");
-
-    let issue_logs = state
-        .database
-        .get_all_latest_issue_logs()
-        .await
-        .expect("Could not get issue logs");
+) -> Result<axum::response::Html<String>, AppError> {
+    let epics = state.database.get_epics().await?;
+
+    let issue_logs = state.database.get_all_latest_issue_logs().await?;
 
+    // Issues of archived epics keep their snapshots but are no longer shown.
     let issues_by_epic = issue_logs
         .iter()
+        .filter(|issue_log| epics.iter().any(|e| e.key == issue_log.epic_key))
         .into_group_map_by(|issue_log| issue_log.epic_key.clone());
 
+    let now = chrono::Utc::now();
+    let mut health_by_epic = HashMap::new();
+    let mut forecast_by_epic = HashMap::new();
+    for epic_key in issues_by_epic.keys() {
+        let epic_logs = state.database.get_logs_of_epic(epic_key).await?;
+        health_by_epic.insert(
+            epic_key.clone(),
+            evaluate_epic(epic_key, &epic_logs, &state.config.health, now)?,
+        );
+        forecast_by_epic.insert(
+            epic_key.clone(),
+            forecast_epic(epic_key, &epic_logs, state.config.forecast.window_days, now)?,
+        );
+    }
+
+    let latest_runs = state.database.get_sync_runs(Some(1)).await?;
+    let last_successful_run = state.database.get_last_successful_sync_run().await?;
+    let sync_status_html = render_sync_status(
+        latest_runs.first(),
+        last_successful_run.as_ref(),
+        state.config.collector.interval_secs,
+        now,
+    )?;
+
     let issues_by_epic_html = issues_by_epic
         .iter()
         .map(|(epic_key, issues)| format!(
-            "<h2>{}</h2><ul>{}</ul>",
-            epics.iter().find(|e| e.key == *epic_key).unwrap().summary,
+            "<h2>{}</h2>
+            {}
+            {}
+            <button hx-get=\"/epic/{}/time_graph.svg\" hx-target=\"this\" hx-swap=\"outerHTML\">View Epic Time Graph</button>
+            <button hx-get=\"/epic/{}/forecast.svg\" hx-target=\"this\" hx-swap=\"outerHTML\">View Forecast Histogram</button>
+            <button hx-get=\"/epic/{}/sprints\" hx-target=\"this\" hx-swap=\"outerHTML\">View Sprints</button>
+            {}
+            <ul>{}</ul>",
+            epics
+                .iter()
+                .find(|e| e.key == *epic_key)
+                .map(|e| e.summary.as_str())
+                .unwrap_or(epic_key),
+            render_health(&health_by_epic[epic_key]),
+            render_forecast(&forecast_by_epic[epic_key]),
+            epic_key,
+            epic_key,
+            epic_key,
+            render_sync_button(&state, Some(epic_key)),
             issues.iter().map(|issue| format!("
             <li>
                 <div>
                     <span>{}: {}</span><br>
+                    <small>{}</small><br>
                     <button hx-get=\"/issue/{}/time_graph.svg\" hx-target=\"this\" hx-swap=\"outerHTML\">View Time Graph</button>
                 </div>
-            </li>", issue.key, issue.summary, issue.key)).collect::<Vec<_>>().join("")
+            </li>", issue.key, issue.summary, render_issue_details(issue), issue.key)).collect::<Vec<_>>().join("")
         ))
         .collect::<Vec<_>>().join("");
 
-    axum::response::Html(format!(
+    Ok(axum::response::Html(format!(
         r#"
         <!DOCTYPE html>
         <html>
@@ -44,12 +224,16 @@ pub async fn root(
             </head>
             <body>
                 <h1>Epics</h1>
+                {}
+                {}
                 <div>
                     {}
                 </div>
             </body>
         </html>
         "#,
+        sync_status_html,
+        render_sync_button(&state, None),
         issues_by_epic_html
-    ))
+    )))
 } 
\ No newline at end of file
@@ -0,0 +1,48 @@
+use crate::collector::collect_data;
+use crate::models::SyncRun;
+use crate::routes::error::escape_html;
+use crate::routes::AppError;
+use crate::AppState;
+use axum::response::IntoResponse;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct SyncQuery {
+    pub epic_key: Option<String>,
+}
+
+/// Collects data right away, limited to one stored epic if given. Fails instead of waiting when
+/// a sync is already running.
+pub async fn sync_now(state: &AppState, epic_key: Option<&str>) -> Result<SyncRun, AppError> {
+    let Some(sync_lock) = &state.sync_lock else {
+        return Err(AppError::SyncDisabled);
+    };
+    if let Some(epic_key) = epic_key {
+        if !state.database.get_epics().await?.iter().any(|e| e.key == epic_key) {
+            return Err(AppError::EpicNotFound(epic_key.to_string()));
+        }
+    }
+    let Ok(_sync_guard) = sync_lock.try_lock() else {
+        return Err(AppError::SyncRunning);
+    };
+
+    collect_data(&state.jira_client, &state.database, &state.config, epic_key)
+        .await
+        .map_err(|e| AppError::SyncFailed(e.to_string()))
+}
+
+/// Sync button of the root page: reloads the page once the sync is done, or replaces the button
+/// with the reason it failed.
+pub async fn sync_button(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    axum::extract::Query(query): axum::extract::Query<SyncQuery>,
+) -> axum::response::Response {
+    match sync_now(&state, query.epic_key.as_deref()).await {
+        Ok(_) => ([("HX-Refresh", "true")], "Synced").into_response(),
+        Err(e) => axum::response::Html(format!(
+            "<span style=\"color: #E74C3C\">Sync failed: {}</span>",
+            escape_html(&e.to_string())
+        ))
+        .into_response(),
+    }
+}
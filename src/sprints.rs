@@ -0,0 +1,124 @@
+use crate::models::{IssueLog, Sprint};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+/// What an epic's issues in a sprint added up to when it started and when it ended.
+#[derive(Serialize)]
+pub struct SprintProgress {
+    pub sprint: Sprint,
+    pub committed_issues: usize,
+    pub committed_estimate: i64,
+    pub committed_story_points: f64,
+    pub completed_issues: usize,
+    pub completed_estimate: i64,
+    pub completed_story_points: f64,
+    /// Time logged on the epic's issues of the sprint between its start and end.
+    pub time_spent: i64,
+}
+
+fn parse_date(value: &str) -> Result<chrono::DateTime<chrono::Utc>, chrono::ParseError> {
+    Ok(chrono::DateTime::parse_from_rfc3339(value)?.with_timezone(&chrono::Utc))
+}
+
+/// The latest snapshot of every issue taken at or before `date`, from snapshots sorted by date.
+fn snapshots_at<'a>(
+    dated_logs: &[(chrono::DateTime<chrono::Utc>, &'a IssueLog)],
+    date: chrono::DateTime<chrono::Utc>,
+) -> HashMap<&'a str, &'a IssueLog> {
+    dated_logs
+        .iter()
+        .take_while(|(logged_at, _)| *logged_at <= date)
+        .map(|(_, log)| (log.key.as_str(), *log))
+        .collect()
+}
+
+/// Start and end of the sprint as far as it has happened, unstarted sprints count as starting now.
+fn sprint_period(
+    sprint: &Sprint,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<(chrono::DateTime<chrono::Utc>, chrono::DateTime<chrono::Utc>), chrono::ParseError> {
+    let start = match &sprint.start_date {
+        Some(start_date) => parse_date(start_date)?.min(now),
+        None => now,
+    };
+    let end = match (&sprint.complete_date, &sprint.end_date) {
+        (Some(complete_date), _) => parse_date(complete_date)?,
+        (None, Some(end_date)) if sprint.state == "closed" => parse_date(end_date)?,
+        _ => now,
+    };
+    Ok((start, end.min(now).max(start)))
+}
+
+/// Progress of the epic in every sprint any of its issues has been part of.
+pub fn epic_sprint_progress(
+    epic_key: &str,
+    issue_logs: &[IssueLog],
+    sprints: Vec<Sprint>,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<Vec<SprintProgress>, chrono::ParseError> {
+    let mut dated_logs = issue_logs
+        .iter()
+        .map(|log| Ok((parse_date(&log.updated_at)?, log)))
+        .collect::<Result<Vec<_>, chrono::ParseError>>()?;
+    dated_logs.sort_by_key(|(date, _)| *date);
+
+    let epic_sprint_ids = issue_logs
+        .iter()
+        .filter(|log| log.epic_key == epic_key)
+        .flat_map(|log| log.sprint_ids.iter().copied())
+        .collect::<HashSet<_>>();
+
+    let mut progress = Vec::new();
+    for sprint in sprints {
+        if !epic_sprint_ids.contains(&sprint.id) {
+            continue;
+        }
+        let (start, end) = sprint_period(&sprint, now)?;
+        let in_sprint =
+            |log: &IssueLog| log.epic_key == epic_key && log.sprint_ids.contains(&sprint.id);
+
+        let at_start = snapshots_at(&dated_logs, start);
+        let committed = at_start
+            .values()
+            .copied()
+            .filter(|log| in_sprint(log))
+            .collect::<Vec<_>>();
+        let at_end = snapshots_at(&dated_logs, end);
+        let ended_in_sprint = at_end
+            .values()
+            .copied()
+            .filter(|log| in_sprint(log))
+            .collect::<Vec<_>>();
+        let completed = ended_in_sprint
+            .iter()
+            .filter(|log| log.is_done())
+            .collect::<Vec<_>>();
+
+        progress.push(SprintProgress {
+            committed_issues: committed.len(),
+            committed_estimate: committed.iter().map(|log| log.time_estimate).sum(),
+            committed_story_points: committed
+                .iter()
+                .filter_map(|log| log.story_points)
+                .fold(0.0, |sum, points| sum + points),
+            completed_issues: completed.len(),
+            completed_estimate: completed.iter().map(|log| log.time_estimate).sum(),
+            completed_story_points: completed
+                .iter()
+                .filter_map(|log| log.story_points)
+                .fold(0.0, |sum, points| sum + points),
+            time_spent: ended_in_sprint
+                .iter()
+                .map(|log| {
+                    let spent_before = at_start
+                        .get(log.key.as_str())
+                        .map(|log| log.time_spent)
+                        .unwrap_or(0);
+                    (log.time_spent - spent_before).max(0)
+                })
+                .sum(),
+            sprint,
+        });
+    }
+    Ok(progress)
+}
@@ -1,12 +1,59 @@
+use crate::config::GraphSize;
+use crate::forecast::Forecast;
+use crate::metrics::{epic_metric_totals_over_time, Metric};
 use crate::models::IssueLog;
 use crate::renderer::{Renderer, Point, GraphStyle};
-use axum;
-use chrono;
+use serde::Deserialize;
 
 pub struct TimeSpentData {
     date: i64,
-    time_spent: i64,
-    time_estimate: i64,
+    /// Values of the plotted pair of metrics.
+    values: [f64; 2],
+    /// Value of the first metric for the issues in the done status category.
+    done: f64,
+}
+
+/// Query parameters of the time graphs, e.g. `?metrics=story_points,remaining_estimate`.
+#[derive(Deserialize)]
+pub struct GraphQuery {
+    metrics: Option<String>,
+}
+
+impl GraphQuery {
+    /// The pair of metrics to plot, `default` unless the query names two others.
+    pub fn metrics(&self, default: [Metric; 2]) -> Result<[Metric; 2], String> {
+        let Some(metrics) = &self.metrics else {
+            return Ok(default);
+        };
+        let metrics = metrics
+            .split(',')
+            .map(|metric| metric.trim().parse::<Metric>())
+            .collect::<Result<Vec<_>, _>>()?;
+        <[Metric; 2]>::try_from(metrics).map_err(|_| "Expected exactly two comma-separated metrics".to_string())
+    }
+}
+
+/// Where the plotted metrics are headed, drawn as dashed lines from the last data point.
+pub struct Projection {
+    date: i64,
+    /// Value of each plotted metric at `date`, `None` for metrics that are not projected.
+    values: [Option<f64>; 2],
+}
+
+impl Projection {
+    /// Time spent grows by the remaining estimate and the remaining estimate drops to zero by the
+    /// expected completion date of the forecast.
+    fn from_forecast(forecast: &Forecast, last: &TimeSpentData, metrics: [Metric; 2]) -> Option<Self> {
+        let values = [0, 1].map(|index| match metrics[index] {
+            Metric::TimeSpent => Some(last.values[index] + forecast.remaining_estimate as f64),
+            Metric::RemainingEstimate => Some(0.0),
+            Metric::OriginalEstimate | Metric::StoryPoints => None,
+        });
+        Some(Self {
+            date: forecast.expected_completion_timestamp()?,
+            values,
+        })
+    }
 }
 
 pub struct TimeGraph {
@@ -20,24 +67,38 @@ impl TimeGraph {
         }
     }
 
-    fn normalize_time_data(&self, time_spent_data: &[TimeSpentData]) -> (Vec<Point>, Vec<Point>) {
-        if time_spent_data.is_empty() {
-            return (Vec::new(), Vec::new());
-        }
+    /// Metrics of the same unit share a scale, otherwise each is scaled to the full height.
+    fn normalize_time_data(
+        &self,
+        time_spent_data: &[TimeSpentData],
+        metrics: [Metric; 2],
+        projection: Option<&Projection>,
+    ) -> (Vec<Point>, Vec<Point>, Vec<Point>, [Vec<Point>; 2]) {
+        let (Some(first), Some(last)) = (time_spent_data.first(), time_spent_data.last()) else {
+            return (Vec::new(), Vec::new(), Vec::new(), [Vec::new(), Vec::new()]);
+        };
 
-        let min_date = time_spent_data.first().unwrap().date;
-        let max_date = time_spent_data.last().unwrap().date;
-        let max_value = time_spent_data
-            .iter()
-            .map(|data| data.time_spent.max(data.time_estimate))
-            .max()
-            .unwrap();
+        let min_date = first.date;
+        let max_date = projection.map_or(last.date, |projection| projection.date.max(last.date));
+        let max_of = |index: usize| {
+            time_spent_data
+                .iter()
+                .map(|data| data.values[index])
+                .chain(projection.and_then(|projection| projection.values[index]))
+                .fold(0.0, f64::max)
+        };
+        let max_values = if metrics[0].is_time() == metrics[1].is_time() {
+            let max_value = max_of(0).max(max_of(1));
+            [max_value, max_value]
+        } else {
+            [max_of(0), max_of(1)]
+        };
 
-        let normalize_value = |value: i64| {
-            if max_value == 0 {
+        let normalize_value = |value: f64, max_value: f64| {
+            if max_value == 0.0 {
                 self.renderer.height as f64
             } else {
-                self.renderer.height as f64 - (value as f64 / max_value as f64) * self.renderer.height as f64
+                self.renderer.height as f64 - (value / max_value) * self.renderer.height as f64
             }
         };
 
@@ -49,36 +110,74 @@ impl TimeGraph {
             }
         };
 
-        let spent_points: Vec<Point> = time_spent_data
-            .iter()
-            .map(|data| Point {
-                x: normalize_x(data.date),
-                y: normalize_value(data.time_spent),
-            })
-            .collect();
+        let points = |value: &dyn Fn(&TimeSpentData) -> f64, max_value: f64| {
+            time_spent_data
+                .iter()
+                .map(|data| Point {
+                    x: normalize_x(data.date),
+                    y: normalize_value(value(data), max_value),
+                })
+                .collect::<Vec<Point>>()
+        };
 
-        let estimate_points: Vec<Point> = time_spent_data
-            .iter()
-            .map(|data| Point {
-                x: normalize_x(data.date),
-                y: normalize_value(data.time_estimate),
-            })
-            .collect();
+        let projected_points = |index: usize| match projection.and_then(|projection| {
+            Some((projection.date, projection.values[index]?))
+        }) {
+            Some((date, value)) => vec![
+                Point {
+                    x: normalize_x(last.date),
+                    y: normalize_value(last.values[index], max_values[index]),
+                },
+                Point {
+                    x: normalize_x(date),
+                    y: normalize_value(value, max_values[index]),
+                },
+            ],
+            None => Vec::new(),
+        };
 
-        (spent_points, estimate_points)
+        (
+            points(&|data| data.values[0], max_values[0]),
+            points(&|data| data.values[1], max_values[1]),
+            points(&|data| data.done, max_values[0]),
+            [projected_points(0), projected_points(1)],
+        )
     }
 
-    pub fn render(&mut self, time_spent_data: Vec<TimeSpentData>) -> String {
-        let (spent_points, estimate_points) = self.normalize_time_data(&time_spent_data);
+    pub fn render(
+        &mut self,
+        time_spent_data: Vec<TimeSpentData>,
+        metrics: [Metric; 2],
+        projection: Option<Projection>,
+    ) -> String {
+        let (first_points, second_points, done_points, [first_projection, second_projection]) =
+            self.normalize_time_data(&time_spent_data, metrics, projection.as_ref());
         
         let graphs = vec![
-            (estimate_points, GraphStyle {
+            (first_points, GraphStyle {
+                stroke_color: "#4A90E2",
+                fill_color: Some("#4A90E2"),
+                dash_array: None,
+            }),
+            (done_points, GraphStyle {
+                stroke_color: "#27AE60",
+                fill_color: Some("#27AE60"),
+                dash_array: None,
+            }),
+            (second_points, GraphStyle {
+                stroke_color: "#E74C3C",
+                fill_color: Some("#E74C3C"),
+                dash_array: None,
+            }),
+            (first_projection, GraphStyle {
                 stroke_color: "#4A90E2",
-                fill_color: "#4A90E2",
+                fill_color: None,
+                dash_array: Some("6 4"),
             }),
-            (spent_points, GraphStyle {
+            (second_projection, GraphStyle {
                 stroke_color: "#E74C3C",
-                fill_color: "#E74C3C", 
+                fill_color: None,
+                dash_array: Some("6 4"),
             }),
         ];
         
@@ -86,21 +185,73 @@ impl TimeGraph {
     }
 }
 
-pub async fn render_issue_time_graph(issue_logs: Vec<IssueLog>) -> axum::response::Html<String> {
-    let mut time_graph = TimeGraph::new(150, 50, 10);
+/// Snapshots are only stored on change, so carries the latest values forward to when the data was
+/// last seen.
+fn extend_to_last_seen(
+    time_spent_data: &mut Vec<TimeSpentData>,
+    last_seen: Option<String>,
+) -> Result<(), chrono::ParseError> {
+    if let (Some(last_seen), Some(last)) = (last_seen, time_spent_data.last()) {
+        let last_seen = chrono::DateTime::parse_from_rfc3339(&last_seen)?.timestamp();
+        if last_seen > last.date {
+            time_spent_data.push(TimeSpentData {
+                date: last_seen,
+                values: last.values,
+                done: last.done,
+            });
+        }
+    }
+    Ok(())
+}
+
+pub fn render_issue_time_graph(
+    issue_logs: Vec<IssueLog>,
+    last_seen: Option<String>,
+    size: &GraphSize,
+    metrics: [Metric; 2],
+) -> Result<axum::response::Html<String>, chrono::ParseError> {
+    let mut time_graph = TimeGraph::new(size.width, size.height, size.margin);
 
     let mut time_spent_data = issue_logs
         .into_iter()
-        .map(|log| TimeSpentData {
-            date: chrono::DateTime::parse_from_rfc3339(&log.updated_at)
-                .unwrap()
-                .timestamp(),
-            time_spent: log.time_spent,
-            time_estimate: log.time_estimate,
+        .map(|log| {
+            Ok(TimeSpentData {
+                date: chrono::DateTime::parse_from_rfc3339(&log.updated_at)?.timestamp(),
+                values: metrics.map(|metric| metric.of(&log)),
+                done: if log.is_done() { metrics[0].of(&log) } else { 0.0 },
+            })
+        })
+        .collect::<Result<Vec<_>, chrono::ParseError>>()?;
+
+    time_spent_data.sort_by_key(|data| data.date);
+    extend_to_last_seen(&mut time_spent_data, last_seen)?;
+
+    Ok(axum::response::Html(time_graph.render(time_spent_data, metrics, None)))
+}
+
+/// Renders the epic over time, with dashed lines towards the expected completion date of the forecast.
+pub fn render_epic_time_graph(
+    epic_key: &str,
+    issue_logs: Vec<IssueLog>,
+    last_seen: Option<String>,
+    size: &GraphSize,
+    metrics: [Metric; 2],
+    forecast: Option<&Forecast>,
+) -> Result<axum::response::Html<String>, chrono::ParseError> {
+    let mut time_graph = TimeGraph::new(size.width, size.height, size.margin);
+
+    let mut time_spent_data = epic_metric_totals_over_time(epic_key, &issue_logs, &metrics)?
+        .into_iter()
+        .map(|totals| TimeSpentData {
+            date: totals.date,
+            values: [totals.values[0], totals.values[1]],
+            done: totals.done_values[0],
         })
         .collect::<Vec<_>>();
+    extend_to_last_seen(&mut time_spent_data, last_seen)?;
+    let projection = forecast.zip(time_spent_data.last()).and_then(|(forecast, last)| {
+        Projection::from_forecast(forecast, last, metrics)
+    });
 
-    time_spent_data.sort_by(|a, b| a.date.cmp(&b.date));
-    
-    axum::response::Html(time_graph.render(time_spent_data))
-} 
\ No newline at end of file
+    Ok(axum::response::Html(time_graph.render(time_spent_data, metrics, projection)))
+}
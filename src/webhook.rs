@@ -0,0 +1,199 @@
+use crate::config::Config;
+use crate::database::{Database, DatabaseError};
+use crate::jira_client::{JiraClient, JiraError};
+use crate::models::{IssueRemoval, JiraIssue, RemovalReason};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum WebhookError {
+    #[error("Invalid webhook payload: {0}")]
+    InvalidPayload(#[from] serde_json::Error),
+    #[error("Jira error: {0}")]
+    Jira(#[from] JiraError),
+    #[error("Database error: {0}")]
+    Database(#[from] DatabaseError),
+}
+
+#[derive(Deserialize)]
+struct WebhookPayload {
+    #[serde(rename = "webhookEvent")]
+    event: String,
+    /// Only parsed for issue events, other events may carry just some of the fields.
+    issue: Option<serde_json::Value>,
+    worklog: Option<WebhookWorklog>,
+}
+
+#[derive(Deserialize)]
+struct WebhookWorklog {
+    #[serde(rename = "issueId")]
+    issue_id: String,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookAction {
+    SnapshotWritten,
+    Unchanged,
+    Removed,
+    /// Events about other things than issues and issues of epics that are not tracked.
+    Ignored,
+}
+
+#[derive(Serialize)]
+pub struct WebhookOutcome {
+    pub event: String,
+    pub issue_key: Option<String>,
+    pub action: WebhookAction,
+}
+
+/// Compares without returning early, so the time taken does not reveal how much of the secret matched.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Checks the `X-Hub-Signature` header Jira Cloud sends for webhooks with a secret, or the secret
+/// as query parameter for Jira Server/Data Center, which cannot sign webhooks.
+pub fn verify(
+    secret: &str,
+    signature: Option<&str>,
+    query_secret: Option<&str>,
+    body: &[u8],
+) -> bool {
+    if let Some(signature) = signature {
+        let Some(Ok(signature)) = signature.strip_prefix("sha256=").map(hex::decode) else {
+            return false;
+        };
+        let mut mac = Hmac::<sha2::Sha256>::new_from_slice(secret.as_bytes())
+            .expect("HMAC accepts keys of any length");
+        mac.update(body);
+        return mac.verify_slice(&signature).is_ok();
+    }
+    query_secret
+        .is_some_and(|query_secret| constant_time_eq(query_secret.as_bytes(), secret.as_bytes()))
+}
+
+/// Whether the issue matches the issues JQL, which webhooks know nothing about.
+async fn matches_issues_jql(
+    jira_client: &JiraClient,
+    config: &Config,
+    issue_key: &str,
+) -> Result<bool, WebhookError> {
+    let jql = format!("{} AND key = \"{}\"", config.jira.issues_jql, issue_key);
+    Ok(!jira_client.get_jql(&jql).await?.is_empty())
+}
+
+/// Stores a snapshot of an issue from a webhook if it belongs to a tracked epic and matches the
+/// issues JQL, or records its removal if it was moved out of one or stopped matching.
+async fn store_issue(
+    jira_client: &JiraClient,
+    database: &Database,
+    config: &Config,
+    issue: &JiraIssue,
+) -> Result<WebhookAction, WebhookError> {
+    let epics = database.get_epics().await?;
+    let is_tracked = |epic_key: &str| epics.iter().any(|epic| epic.key == epic_key);
+
+    let reason = if issue.fields.epic_key.as_deref().is_some_and(is_tracked) {
+        if matches_issues_jql(jira_client, config, &issue.key).await? {
+            return Ok(if database.insert_issue(issue).await? {
+                WebhookAction::SnapshotWritten
+            } else {
+                WebhookAction::Unchanged
+            });
+        }
+        RemovalReason::NoLongerMatchesJql
+    } else {
+        RemovalReason::MovedToOtherEpic
+    };
+    match database.get_latest_issue_log(&issue.key).await? {
+        Some(latest) if is_tracked(&latest.epic_key) => {
+            record_removal(database, &issue.key, latest.epic_key, reason).await
+        }
+        _ => Ok(WebhookAction::Ignored),
+    }
+}
+
+async fn record_removal(
+    database: &Database,
+    issue_key: &str,
+    epic_key: String,
+    reason: RemovalReason,
+) -> Result<WebhookAction, WebhookError> {
+    println!(
+        "Issue {} was removed from epic {}: {}",
+        issue_key,
+        epic_key,
+        reason.as_str()
+    );
+    database
+        .insert_issue_removals(vec![IssueRemoval {
+            key: issue_key.to_string(),
+            epic_key,
+            removed_at: chrono::Utc::now().to_rfc3339(),
+            reason,
+        }])
+        .await?;
+    Ok(WebhookAction::Removed)
+}
+
+/// Applies a Jira webhook to the stored data. Polling still reconciles what webhooks cannot tell,
+/// like epics that stop matching the epics JQL.
+pub async fn handle_webhook(
+    jira_client: &JiraClient,
+    database: &Database,
+    config: &Config,
+    body: &[u8],
+) -> Result<WebhookOutcome, WebhookError> {
+    let payload = serde_json::from_slice::<WebhookPayload>(body)?;
+
+    let (issue_key, action) = match (payload.event.as_str(), payload.issue, payload.worklog) {
+        ("jira:issue_created" | "jira:issue_updated", Some(issue), _) => {
+            let issue = jira_client
+                .resolve_issue(serde_json::from_value(issue)?)
+                .await?;
+            (
+                Some(issue.key.clone()),
+                store_issue(jira_client, database, config, &issue).await?,
+            )
+        }
+        ("jira:issue_deleted", Some(issue), _) => {
+            let issue = serde_json::from_value::<JiraIssue>(issue)?;
+            let action = match database.get_latest_issue_log(&issue.key).await? {
+                Some(latest) => {
+                    record_removal(
+                        database,
+                        &issue.key,
+                        latest.epic_key,
+                        RemovalReason::Deleted,
+                    )
+                    .await?
+                }
+                None => WebhookAction::Ignored,
+            };
+            (Some(issue.key), action)
+        }
+        // Worklog events only carry the issue ID, the aggregated time tracking has to be fetched.
+        ("worklog_created" | "worklog_updated" | "worklog_deleted", _, Some(worklog)) => {
+            let issue = jira_client.get_issue(&worklog.issue_id).await?;
+            (
+                Some(issue.key.clone()),
+                store_issue(jira_client, database, config, &issue).await?,
+            )
+        }
+        _ => (None, WebhookAction::Ignored),
+    };
+
+    println!(
+        "Webhook {} for {}: {:?}",
+        payload.event,
+        issue_key.as_deref().unwrap_or("-"),
+        action
+    );
+    Ok(WebhookOutcome {
+        event: payload.event,
+        issue_key,
+        action,
+    })
+}
@@ -0,0 +1,172 @@
+//! Records a collection from the mock Jira to a cassette and replays it without Jira.
+
+mod common;
+
+use common::mock_jira::MockJira;
+use common::{demo_fixtures, jira_config, start_mock_jira, Server, TempDir};
+use std::sync::Arc;
+
+fn collect(server: &Server) -> std::process::Output {
+    server.run(&["collect"])
+}
+
+fn issue_keys(server: &Server) -> Vec<String> {
+    server.get_json("/api/v1/issues?per_page=100")["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|issue| issue["key"].as_str().unwrap().to_string())
+        .collect()
+}
+
+fn cassette_config(jira_url: &str, mode: &str, dir: &TempDir) -> String {
+    format!(
+        "{}cassette_mode = {:?}\ncassette_dir = {:?}\n",
+        jira_config(jira_url),
+        mode,
+        dir.path()
+    )
+}
+
+#[test]
+fn replays_recorded_collection_without_jira() {
+    let cassette = TempDir::new("cassette-replay-recording");
+    let mock = Arc::new(MockJira::new(demo_fixtures(), 4));
+    let jira_url = start_mock_jira(mock.clone());
+
+    let recording = Server::start(
+        "cassette-record",
+        &cassette_config(&jira_url, "record", &cassette),
+        &["serve", "--no-collector"],
+    );
+    assert!(collect(&recording).status.success());
+    let recorded_requests = mock.requests().len();
+    assert!(recorded_requests > 0);
+
+    // Nothing listens on port 1, every response has to come from the cassette.
+    let replay = Server::start(
+        "cassette-replay",
+        &cassette_config("http://127.0.0.1:1", "replay", &cassette)
+            .replace("token = \"test\"\n", ""),
+        &["serve", "--no-collector"],
+    );
+    let output = collect(&replay);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+    assert_eq!(mock.requests().len(), recorded_requests);
+    assert_eq!(issue_keys(&replay), issue_keys(&recording));
+    assert_eq!(
+        replay.get_json("/api/v1/epics/DEMO-2/metrics"),
+        recording.get_json("/api/v1/epics/DEMO-2/metrics")
+    );
+}
+
+/// Stores the same previous sync in both databases, so both collect incrementally from there.
+fn set_last_sync(servers: &[&Server], last_sync_at: &str) {
+    for server in servers {
+        server
+            .database()
+            .execute(
+                "UPDATE sync_state SET value = ? WHERE name = 'last_sync_at'",
+                [last_sync_at],
+            )
+            .unwrap();
+    }
+}
+
+#[test]
+fn replays_recorded_incremental_sync() {
+    let cassette = TempDir::new("cassette-incremental-recording");
+    let mock = Arc::new(MockJira::new(demo_fixtures(), 50));
+    let jira_url = start_mock_jira(mock.clone());
+    let recording = Server::start(
+        "cassette-incremental-record",
+        &cassette_config(&jira_url, "record", &cassette),
+        &["serve", "--no-collector"],
+    );
+    let replay = Server::start(
+        "cassette-incremental-replay",
+        &cassette_config("http://127.0.0.1:1", "replay", &cassette),
+        &["serve", "--no-collector"],
+    );
+    assert!(collect(&recording).status.success());
+    assert!(collect(&replay).status.success());
+
+    let last_sync_at = (chrono::Utc::now() - chrono::Duration::hours(1)).to_rfc3339();
+    set_last_sync(&[&recording, &replay], &last_sync_at);
+    mock.update(|fixtures| {
+        let issue = fixtures
+            .issues
+            .iter_mut()
+            .find(|issue| issue["key"] == "DEMO-12")
+            .unwrap();
+        issue["fields"]["aggregatetimespent"] = 72000.into();
+    });
+    assert!(collect(&recording).status.success());
+    let recorded_requests = mock.requests().len();
+
+    let output = collect(&replay);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+    assert_eq!(mock.requests().len(), recorded_requests);
+    let run = &replay.get_json("/api/v1/sync-runs")["items"][0];
+    assert_eq!(run["full_sync"], false);
+    assert_eq!(run["snapshots_written"], 1);
+    let history = replay.get_json("/api/v1/issues/DEMO-12/history");
+    assert_eq!(history["items"][1]["time_spent"], 72000);
+}
+
+#[test]
+fn scrubs_credentials_from_recordings() {
+    let cassette = TempDir::new("cassette-scrub");
+    let jira_url = start_mock_jira(Arc::new(MockJira::new(demo_fixtures(), 50)));
+    let server = Server::start(
+        "cassette-scrub-server",
+        &cassette_config(&jira_url, "record", &cassette),
+        &["serve", "--no-collector"],
+    );
+    assert!(collect(&server).status.success());
+
+    let files = std::fs::read_dir(cassette.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect::<Vec<_>>();
+    assert!(!files.is_empty());
+    for file in files {
+        let name = file.file_name().unwrap().to_str().unwrap().to_string();
+        assert!(
+            name.starts_with("GET-") && name.ends_with(".json"),
+            "{}",
+            name
+        );
+
+        let interaction: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&file).unwrap()).unwrap();
+        assert_eq!(
+            interaction["request_headers"]["authorization"],
+            "[scrubbed]"
+        );
+        assert!(!interaction.to_string().contains("Bearer test"));
+        assert!(interaction["path"].as_str().unwrap().starts_with("/rest/"));
+    }
+}
+
+#[test]
+fn fails_on_requests_missing_from_the_cassette() {
+    let cassette = TempDir::new("cassette-missing");
+    let server = Server::start(
+        "cassette-missing-server",
+        &cassette_config("http://127.0.0.1:1", "replay", &cassette),
+        &["serve", "--no-collector"],
+    );
+
+    let output = collect(&server);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("No response recorded for GET"));
+}
@@ -0,0 +1,476 @@
+//! Runs the collector against the mock Jira serving the demo fixtures and checks what it stored.
+
+mod common;
+
+use common::mock_jira::MockJira;
+use common::{demo_fixtures, jira_config, start_mock_jira, Server};
+use std::sync::Arc;
+
+/// Starts the server with `extra_config` appended to the `[jira]` section.
+fn start(name: &str, mock: &Arc<MockJira>, extra_config: &str) -> Server {
+    let jira_url = start_mock_jira(mock.clone());
+    Server::start(
+        name,
+        &format!("{}{}", jira_config(&jira_url), extra_config),
+        &["serve", "--no-collector"],
+    )
+}
+
+fn collect(server: &Server, args: &[&str]) {
+    let output = server.run(&[&["collect"], args].concat());
+    assert!(
+        output.status.success(),
+        "collect failed: {}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+}
+
+fn keys(page: &serde_json::Value) -> Vec<String> {
+    page["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["key"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn collects_epics_and_issues_across_pages() {
+    let mock = Arc::new(MockJira::new(demo_fixtures(), 3));
+    let server = start("collector-pages", &mock, "");
+    collect(&server, &[]);
+
+    assert_eq!(
+        keys(&server.get_json("/api/v1/epics")),
+        ["DEMO-1", "DEMO-2", "DEMO-3"]
+    );
+    assert_eq!(server.get_json("/api/v1/issues?per_page=100")["total"], 10);
+    assert!(mock
+        .requests()
+        .iter()
+        .any(|request| request.contains("/search?") && request.contains("startAt=9")));
+
+    let issue = &server.get_json("/api/v1/epics/DEMO-1/issues")["items"][0];
+    assert_eq!(issue["key"], "DEMO-11");
+    assert_eq!(issue["story_points"], 3.0);
+    assert_eq!(issue["time_estimate"], 57600);
+
+    let run = &server.get_json("/api/v1/sync-runs")["items"][0];
+    assert_eq!(run["full_sync"], true);
+    assert_eq!(run["epics_fetched"], 3);
+    assert_eq!(run["issues_fetched"], 10);
+    assert_eq!(run["snapshots_written"], 10);
+    assert!(run["error"].is_null());
+}
+
+#[test]
+fn collects_with_page_tokens_of_api_version_3() {
+    let mock = Arc::new(MockJira::new(demo_fixtures(), 4));
+    let server = start("collector-api3", &mock, "api_version = 3\n");
+    collect(&server, &[]);
+
+    assert_eq!(server.get_json("/api/v1/issues?per_page=100")["total"], 10);
+    assert!(mock
+        .requests()
+        .iter()
+        .any(|request| request.contains("/rest/api/3/search/jql?")
+            && request.contains("nextPageToken=8")));
+}
+
+#[test]
+fn stores_only_changed_snapshots() {
+    let mock = Arc::new(MockJira::new(demo_fixtures(), 50));
+    let server = start("collector-unchanged", &mock, "");
+    collect(&server, &[]);
+    collect(&server, &[]);
+    assert_eq!(server.query_count("SELECT COUNT(*) FROM issues"), 10);
+
+    mock.update(|fixtures| {
+        let issue = fixtures
+            .issues
+            .iter_mut()
+            .find(|issue| issue["key"] == "DEMO-12")
+            .unwrap();
+        issue["fields"]["aggregatetimespent"] = 72000.into();
+    });
+    collect(&server, &[]);
+
+    assert_eq!(server.query_count("SELECT COUNT(*) FROM issues"), 11);
+    let history = server.get_json("/api/v1/issues/DEMO-12/history");
+    assert_eq!(history["total"], 2);
+    assert_eq!(
+        server.get_json("/api/v1/sync-runs")["items"][0]["snapshots_written"],
+        1
+    );
+}
+
+/// The JQL of the searches the collector sent since the `skip` first requests.
+fn searched_jql(mock: &MockJira, skip: usize) -> Vec<String> {
+    mock.requests()
+        .into_iter()
+        .skip(skip)
+        .filter(|request| request.contains("/search"))
+        .filter_map(|request| {
+            let url = reqwest::Url::parse(&format!("http://jira{}", request)).unwrap();
+            url.query_pairs()
+                .find(|(name, _)| name == "jql")
+                .map(|(_, jql)| jql.into_owned())
+        })
+        .collect()
+}
+
+#[test]
+fn encodes_the_jql_of_searches() {
+    for api_version in [2, 3] {
+        let mock = Arc::new(MockJira::new(demo_fixtures(), 4));
+        let jira_url = start_mock_jira(mock.clone());
+        let config = jira_config(&jira_url).replace(
+            "issues_jql = \"project = DEMO\"",
+            "issues_jql = 'project = DEMO AND labels != \"R&D\" AND summary !~ \"a+b\"'",
+        );
+        let server = Server::start(
+            &format!("collector-encoded-jql-{}", api_version),
+            &format!("{}api_version = {}\n", config, api_version),
+            &["serve", "--no-collector"],
+        );
+        collect(&server, &[]);
+
+        assert_eq!(server.get_json("/api/v1/issues?per_page=100")["total"], 10);
+        let issues_jql = "project = DEMO AND labels != \"R&D\" AND summary !~ \"a+b\" AND ";
+        assert!(searched_jql(&mock, 0)
+            .iter()
+            .any(|jql| jql.starts_with(issues_jql)));
+    }
+}
+
+#[test]
+fn syncs_without_any_matching_epics() {
+    let mock = Arc::new(MockJira::new(demo_fixtures(), 50));
+    mock.update(|fixtures| fixtures.epics.clear());
+    let server = start("collector-no-epics", &mock, "");
+    collect(&server, &[]);
+
+    let run = &server.get_json("/api/v1/sync-runs")["items"][0];
+    assert_eq!(run["full_sync"], true);
+    assert_eq!(run["epics_fetched"], 0);
+    assert_eq!(run["issues_fetched"], 0);
+    assert!(run["error"].is_null());
+    // Only the epics were searched, the issues JQL would have ended in `IN ()`.
+    assert_eq!(searched_jql(&mock, 0).len(), 1);
+}
+
+#[test]
+fn fetches_all_issues_of_new_epics_in_incremental_syncs() {
+    let fixtures = demo_fixtures();
+    let new_epic = fixtures.epics[2].clone();
+    let mock = Arc::new(MockJira::new(fixtures, 50));
+    mock.update(|fixtures| fixtures.epics.retain(|epic| epic["key"] != "DEMO-3"));
+    let server = start("collector-new-epic", &mock, "");
+    collect(&server, &[]);
+
+    mock.update(|fixtures| fixtures.epics.push(new_epic));
+    let skip = mock.requests().len();
+    collect(&server, &[]);
+
+    assert_eq!(
+        server.get_json("/api/v1/sync-runs")["items"][0]["full_sync"],
+        false
+    );
+    let jql = searched_jql(&mock, skip);
+    assert!(jql
+        .iter()
+        .any(|jql| jql.contains("DEMO-1,DEMO-2") && jql.contains("updated >=")));
+    assert!(jql
+        .iter()
+        .any(|jql| jql.contains("IN (DEMO-3)") && !jql.contains("updated")));
+    assert_eq!(
+        keys(&server.get_json("/api/v1/epics/DEMO-3/issues")),
+        ["DEMO-31", "DEMO-32", "DEMO-33"]
+    );
+}
+
+#[test]
+fn retries_failed_requests() {
+    let mock = Arc::new(MockJira::new(demo_fixtures(), 50));
+    mock.fail("/rest/api/2/search", 503, 2);
+    mock.fail("/rest/api/2/field", 429, 1);
+    let server = start("collector-retries", &mock, "");
+    collect(&server, &[]);
+
+    assert_eq!(server.get_json("/api/v1/issues?per_page=100")["total"], 10);
+}
+
+#[test]
+fn gives_up_when_rate_limited_for_longer_than_the_max_delay() {
+    let mock = Arc::new(MockJira::new(demo_fixtures(), 50));
+    mock.rate_limit("/rest/api/2/search", 120, 1);
+    let server = start("collector-rate-limited", &mock, "retry_max_delay_ms = 1000\n");
+
+    assert!(!server.run(&["collect"]).status.success());
+    let run = &server.get_json("/api/v1/sync-runs")["items"][0];
+    assert!(run["error"].as_str().unwrap().contains("Rate limited"), "{}", run);
+    let searches = mock
+        .requests()
+        .iter()
+        .filter(|request| request.starts_with("/rest/api/2/search"))
+        .count();
+    assert_eq!(searches, 1);
+}
+
+#[test]
+fn records_failed_syncs() {
+    let mock = Arc::new(MockJira::new(demo_fixtures(), 50));
+    mock.fail("/rest/api/2/search", 500, 1);
+    let server = start("collector-failure", &mock, "max_retries = 0\n");
+
+    assert!(!server.run(&["collect"]).status.success());
+    let run = &server.get_json("/api/v1/sync-runs")["items"][0];
+    assert!(run["finished_at"].is_string());
+    assert!(run["error"].as_str().unwrap().contains("500"));
+    assert_eq!(server.query_count("SELECT COUNT(*) FROM epics"), 0);
+
+    // The failure was used up, so the next sync succeeds.
+    collect(&server, &[]);
+    assert!(server.get_json("/api/v1/sync-runs")["items"][0]["error"].is_null());
+}
+
+#[test]
+fn records_removed_issues_and_archives_epics() {
+    let mock = Arc::new(MockJira::new(demo_fixtures(), 50));
+    let server = start("collector-removals", &mock, "");
+    collect(&server, &[]);
+
+    mock.update(|fixtures| {
+        fixtures.issues.retain(|issue| issue["key"] != "DEMO-14");
+        let moved = fixtures
+            .issues
+            .iter_mut()
+            .find(|issue| issue["key"] == "DEMO-13")
+            .unwrap();
+        moved["fields"]["customfield_10014"] = "OTHER-1".into();
+        fixtures.epics.retain(|epic| epic["key"] != "DEMO-3");
+    });
+    // Removals are only noticed by full syncs.
+    server
+        .database()
+        .execute(
+            "DELETE FROM sync_state WHERE name = 'last_full_sync_at'",
+            (),
+        )
+        .unwrap();
+    collect(&server, &[]);
+
+    assert_eq!(
+        keys(&server.get_json("/api/v1/epics")),
+        ["DEMO-1", "DEMO-2"]
+    );
+    assert_eq!(
+        keys(&server.get_json("/api/v1/epics/DEMO-1/issues")),
+        ["DEMO-11", "DEMO-12"]
+    );
+    let removals = server.get_json("/api/v1/removals");
+    let mut reasons = removals["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|removal| {
+            format!(
+                "{} {}",
+                removal["key"].as_str().unwrap(),
+                removal["reason"].as_str().unwrap()
+            )
+        })
+        .collect::<Vec<_>>();
+    reasons.sort();
+    assert_eq!(reasons, ["DEMO-13 moved_to_other_epic", "DEMO-14 deleted"]);
+    assert_eq!(
+        server.query_count("SELECT COUNT(*) FROM epics WHERE archived_at IS NOT NULL"),
+        1
+    );
+}
+
+#[test]
+fn collects_a_single_epic() {
+    let mock = Arc::new(MockJira::new(demo_fixtures(), 50));
+    let server = start("collector-epic", &mock, "");
+    collect(&server, &["--epic", "DEMO-2"]);
+
+    assert_eq!(keys(&server.get_json("/api/v1/epics")), ["DEMO-2"]);
+    assert_eq!(
+        keys(&server.get_json("/api/v1/issues")),
+        ["DEMO-21", "DEMO-22", "DEMO-23"]
+    );
+    assert_eq!(
+        server.get_json("/api/v1/sync-runs")["items"][0]["epic_key"],
+        "DEMO-2"
+    );
+
+    assert!(!server
+        .run(&["collect", "--epic", "DEMO-9"])
+        .status
+        .success());
+}
+
+#[test]
+fn backfills_history_from_changelogs_and_worklogs() {
+    let mock = Arc::new(MockJira::new(demo_fixtures(), 2));
+    let server = start("collector-backfill", &mock, "");
+    collect(&server, &[]);
+    let output = server.run(&["backfill", "--epic", "DEMO-1"]);
+    assert!(output.status.success());
+
+    let history = server.get_json("/api/v1/issues/DEMO-11/history?per_page=100");
+    assert!(history["total"].as_u64().unwrap() > 1);
+    // 14 hours were logged in 4 hour worklogs, paged two at a time.
+    assert!(mock
+        .requests()
+        .iter()
+        .any(|request| request.starts_with("/rest/api/2/issue/DEMO-11/worklog?startAt=2")));
+    assert_eq!(
+        server.query_count("SELECT MIN(time_spent) FROM issues WHERE key = 'DEMO-11'"),
+        0
+    );
+}
+
+#[test]
+fn backfill_pages_through_changelogs() {
+    let mock = Arc::new(MockJira::new(demo_fixtures(), 1));
+    let server = start("collector-backfill-changelog", &mock, "");
+    collect(&server, &[]);
+    assert!(server.run(&["backfill", "--epic", "DEMO-1"]).status.success());
+
+    assert!(mock
+        .requests()
+        .iter()
+        .any(|request| request.starts_with("/rest/api/2/issue/DEMO-11/changelog?startAt=1")));
+    // The re-estimate is the second entry of the changelog.
+    assert_eq!(
+        server.query_count(
+            "SELECT COUNT(*) FROM issues WHERE key = 'DEMO-11' AND time_estimate = 57600 AND updated_at LIKE '2026-10-01%'"
+        ),
+        1
+    );
+}
+
+#[test]
+fn backfills_statuses_and_skips_issues_with_subtasks() {
+    let mock = Arc::new(MockJira::new(demo_fixtures(), 50));
+    mock.update(|fixtures| {
+        let parent = fixtures
+            .issues
+            .iter_mut()
+            .find(|issue| issue["key"] == "DEMO-13")
+            .unwrap();
+        parent["fields"]["subtasks"] = serde_json::json!([{ "key": "DEMO-15" }]);
+        fixtures.changelogs.get_mut("DEMO-12").unwrap().push(serde_json::json!({
+            "id": "12",
+            "created": "2026-09-20T10:00:00.000+0000",
+            "items": [{
+                "field": "status",
+                "from": "10000",
+                "fromString": "To Do",
+                "to": "3",
+                "toString": "In Progress"
+            }]
+        }));
+    });
+    let server = start("collector-backfill-status", &mock, "");
+    collect(&server, &[]);
+    let output = server.run(&["backfill", "--epic", "DEMO-1"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Skipping DEMO-13"));
+
+    assert_eq!(
+        server.query_count("SELECT COUNT(*) FROM issues WHERE key = 'DEMO-13'"),
+        1
+    );
+    assert!(
+        server.query_count(
+            "SELECT COUNT(*) FROM issues WHERE key = 'DEMO-12' AND status = 'To Do' AND status_category = ''"
+        ) > 0
+    );
+    assert_eq!(
+        server.query_count(
+            "SELECT COUNT(*) FROM issues WHERE key = 'DEMO-12' AND status = 'In Progress'
+            AND status_category = 'indeterminate' AND updated_at LIKE '2026-09-20%'"
+        ),
+        1
+    );
+}
+
+#[test]
+fn schema_is_migrated_to_the_latest_version() {
+    let mock = Arc::new(MockJira::new(demo_fixtures(), 50));
+    let server = start("collector-schema", &mock, "");
+    let output = String::from_utf8(server.run(&["schema-version"]).stdout).unwrap();
+    let versions = output
+        .lines()
+        .filter_map(|line| line.split_once(": "))
+        .map(|(_, version)| version.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(versions.len(), 2, "{}", output);
+    assert_eq!(versions[0], versions[1]);
+}
+
+#[test]
+fn escapes_sprint_names_from_jira() {
+    let mock = Arc::new(MockJira::new(demo_fixtures(), 50));
+    mock.update(|fixtures| {
+        fixtures.sprints.get_mut("1").unwrap()[1]["name"] = "Sprint <12> & co".into();
+    });
+    let server = start("collector-sprint-names", &mock, "");
+    collect(&server, &[]);
+
+    let page = reqwest::blocking::get(server.url("/epic/DEMO-1/sprints"))
+        .unwrap()
+        .text()
+        .unwrap();
+    assert!(page.contains("Sprint &lt;12&gt; &amp; co"), "{}", page);
+    assert!(!page.contains("<12>"));
+}
+
+#[test]
+fn escapes_issue_details_from_jira() {
+    let mock = Arc::new(MockJira::new(demo_fixtures(), 50));
+    mock.update(|fixtures| {
+        let issue = fixtures
+            .issues
+            .iter_mut()
+            .find(|issue| issue["key"] == "DEMO-12")
+            .unwrap();
+        issue["fields"]["labels"] = serde_json::json!(["<img/src=x>"]);
+        issue["fields"]["priority"]["name"] = "<b>High</b>".into();
+    });
+    let server = start("collector-issue-details", &mock, "");
+    collect(&server, &[]);
+
+    let page = reqwest::blocking::get(server.url("/"))
+        .unwrap()
+        .text()
+        .unwrap();
+    assert!(page.contains("&lt;b&gt;High&lt;/b&gt; · &lt;img/src=x&gt;"), "{}", page);
+    assert!(!page.contains("<img/src"));
+}
+
+#[test]
+fn exports_sprint_ids_as_json_and_csv() {
+    let mock = Arc::new(MockJira::new(demo_fixtures(), 50));
+    let server = start("collector-export", &mock, "");
+    collect(&server, &[]);
+
+    let json = server.run(&["export", "--epic", "DEMO-1"]);
+    let json: serde_json::Value = serde_json::from_slice(&json.stdout).unwrap();
+    let issue = json["issues"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|issue| issue["key"] == "DEMO-12")
+        .unwrap();
+    assert_eq!(issue["sprint_ids"], serde_json::json!([11, 12]));
+
+    let csv = server.run(&["export", "--format", "csv", "--epic", "DEMO-1"]);
+    let csv = String::from_utf8(csv.stdout).unwrap();
+    assert!(csv.lines().next().unwrap().ends_with(",story_points,sprint_ids"));
+    let row = csv.lines().find(|line| line.starts_with("DEMO-12,")).unwrap();
+    assert!(row.ends_with(",5,11 12"), "{}", row);
+}
@@ -0,0 +1,260 @@
+//! Helpers to run the binary against a temporary database and a stand-in for Jira.
+
+#![allow(dead_code)]
+
+#[path = "../../src/mock_jira.rs"]
+pub mod mock_jira;
+
+use mock_jira::{Fixtures, MockJira};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+pub fn free_port() -> u16 {
+    std::net::TcpListener::bind("127.0.0.1:0")
+        .expect("Could not bind to a free port")
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+/// A directory under the system temp dir that is removed again when dropped.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "jira-epic-healthchecker-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).expect("Could not create temp dir");
+        Self(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// The binary serving the dashboard and API, killed when dropped.
+pub struct Server {
+    child: Child,
+    pub base_url: String,
+    pub database_path: PathBuf,
+    config_path: PathBuf,
+    dir: TempDir,
+}
+
+impl Server {
+    /// Starts `args` with `config` as configuration file, in a fresh directory so no `.env` or
+    /// `config.toml` of the developer is picked up, and waits until the API answers.
+    pub fn start(name: &str, config: &str, args: &[&str]) -> Self {
+        let dir = TempDir::new(name);
+        let port = free_port();
+        let database_path = dir.path().join("test.db");
+        let config_path = dir.path().join("config.toml");
+        std::fs::write(
+            &config_path,
+            format!(
+                "{}\n[server]\nbind_address = \"127.0.0.1:{}\"\n\n[database]\npath = {:?}\n",
+                config, port, database_path
+            ),
+        )
+        .expect("Could not write config");
+
+        let child = command(dir.path(), &config_path, args)
+            .stdout(Stdio::null())
+            .spawn()
+            .expect("Could not start server");
+
+        let server = Self {
+            child,
+            base_url: format!("http://127.0.0.1:{}", port),
+            database_path,
+            config_path,
+            dir,
+        };
+        server.wait_until_ready();
+        server
+    }
+
+    fn wait_until_ready(&self) {
+        let started = Instant::now();
+        while started.elapsed() < Duration::from_secs(10) {
+            if reqwest::blocking::get(self.url("/api/v1/epics"))
+                .is_ok_and(|r| r.status().is_success())
+            {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        panic!("Server did not start within 10 seconds");
+    }
+
+    /// Waits until the collector started with the server has finished its first sync.
+    pub fn wait_for_first_sync(&self) {
+        let started = Instant::now();
+        while started.elapsed() < Duration::from_secs(10) {
+            let runs = self.get_json("/api/v1/sync-runs");
+            if let Some(run) = runs["items"].as_array().and_then(|runs| runs.first()) {
+                assert!(run["error"].is_null(), "Sync failed: {}", run["error"]);
+                if run["finished_at"].is_string() {
+                    return;
+                }
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        panic!("The first sync did not finish within 10 seconds");
+    }
+
+    /// Runs another command of the binary with the same configuration and database, e.g. `collect`.
+    pub fn run(&self, args: &[&str]) -> Output {
+        command(self.dir.path(), &self.config_path, args)
+            .output()
+            .expect("Could not run command")
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    pub fn get_json(&self, path: &str) -> serde_json::Value {
+        let response = reqwest::blocking::get(self.url(path)).expect("Request failed");
+        assert!(
+            response.status().is_success(),
+            "GET {} returned {}",
+            path,
+            response.status()
+        );
+        response.json().expect("Response is not JSON")
+    }
+
+    pub fn database(&self) -> rusqlite::Connection {
+        rusqlite::Connection::open(&self.database_path).expect("Could not open database")
+    }
+
+    /// Runs a query returning a single number, e.g. a count.
+    pub fn query_count(&self, query: &str) -> i64 {
+        self.database()
+            .query_row(query, (), |row| row.get(0))
+            .unwrap_or_else(|e| panic!("Could not run {}: {}", query, e))
+    }
+
+    /// Stores an epic directly, as if a sync had fetched it.
+    pub fn insert_epic(&self, key: &str, summary: &str) {
+        self.database()
+            .execute(
+                "INSERT INTO epics (key, summary, updated_at) VALUES (?, ?, ?)",
+                (key, summary, "2024-01-01T00:00:00+00:00"),
+            )
+            .expect("Could not insert epic");
+    }
+
+    /// Stores a snapshot of an issue directly, as if it had been collected at `updated_at`.
+    pub fn insert_snapshot(
+        &self,
+        key: &str,
+        epic_key: &str,
+        assignee: &str,
+        time_spent: i64,
+        remaining_estimate: i64,
+        updated_at: chrono::DateTime<chrono::Utc>,
+    ) {
+        self.database()
+            .execute(
+                "INSERT INTO issues (key, summary, epic_key, time_estimate, time_spent, remaining_estimate, updated_at, assignee)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+                (
+                    key,
+                    key,
+                    epic_key,
+                    time_spent + remaining_estimate,
+                    time_spent,
+                    remaining_estimate,
+                    updated_at.to_rfc3339(),
+                    assignee,
+                ),
+            )
+            .expect("Could not insert issue snapshot");
+    }
+
+    /// Serves without the collector, with `epic_key` stored to insert snapshots into.
+    pub fn start_with_epic(name: &str, config: &str, epic_key: &str) -> Self {
+        let server = Self::start(name, config, &["serve", "--no-collector"]);
+        server.insert_epic(epic_key, epic_key);
+        server
+    }
+}
+
+/// Seconds in an hour, the unit estimates and time spent are stored in.
+pub const HOUR: i64 = 3600;
+
+/// A minute earlier than `days` days ago, so snapshots count towards that day.
+pub fn days_ago(days: i64) -> chrono::DateTime<chrono::Utc> {
+    chrono::Utc::now() - chrono::Duration::days(days) - chrono::Duration::minutes(1)
+}
+
+fn command(dir: &Path, config_path: &Path, args: &[&str]) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_jira-epic-healthchecker_rust"));
+    command
+        .args(args)
+        .current_dir(dir)
+        .env("CONFIG_PATH", config_path);
+    command
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Serves the mock Jira on a free port in a thread of its own. Returns the base URL to configure
+/// as `jira.base_url`.
+pub fn start_mock_jira(mock: Arc<MockJira>) -> String {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("Could not bind mock Jira");
+    listener.set_nonblocking(true).unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+
+    std::thread::spawn(move || {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async move {
+            let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+            mock.serve(listener).await.unwrap();
+        });
+    });
+    base_url
+}
+
+/// Configuration of a Jira at `base_url` with the fields of the demo fixtures, retrying quickly.
+pub fn jira_config(base_url: &str) -> String {
+    format!(
+        "[jira]\nbase_url = {:?}\ntoken = \"test\"\nepics_jql = \"project = DEMO\"\nissues_jql = \"project = DEMO\"\nstory_points_field = \"Story Points\"\nboard_ids = [1]\nretry_base_delay_ms = 1\n",
+        base_url
+    )
+}
+
+pub fn demo_fixtures_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/demo")
+}
+
+pub fn demo_fixtures() -> Fixtures {
+    Fixtures::load(&demo_fixtures_dir()).expect("Could not load the demo fixtures")
+}
+
+pub fn fixture(path: &str) -> Vec<u8> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(path);
+    std::fs::read(&path).unwrap_or_else(|e| panic!("Could not read {}: {}", path.display(), e))
+}
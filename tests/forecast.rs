@@ -0,0 +1,148 @@
+//! Forecasts completion dates from snapshot histories stored directly in the database.
+
+mod common;
+
+use common::{days_ago, Server, HOUR};
+
+fn date_of(timestamp: &serde_json::Value) -> String {
+    timestamp.as_str().unwrap()[..10].to_string()
+}
+
+/// 80 hours remaining ten days ago, worked down by 4 hours every day since.
+fn insert_steady_burn(server: &Server) {
+    for day in 0..=10 {
+        server.insert_snapshot(
+            "FC-11",
+            "FC-1",
+            "alice",
+            day * 4 * HOUR,
+            (80 - day * 4) * HOUR,
+            days_ago(10 - day),
+        );
+    }
+}
+
+#[test]
+fn projects_completion_from_steady_burn() {
+    let server = Server::start_with_epic("forecast-steady", "", "FC-1");
+    insert_steady_burn(&server);
+
+    let forecast = server.get_json("/api/v1/epics/FC-1/forecast");
+    assert_eq!(forecast["status"], "projected");
+    assert_eq!(forecast["window_days"], 10);
+    assert_eq!(forecast["remaining_estimate"], 40 * HOUR);
+    assert_eq!(forecast["remaining_burn_per_day"], 4.0 * HOUR as f64);
+    assert_eq!(forecast["time_spent_per_day"], 4.0 * HOUR as f64);
+
+    let expected = (chrono::Utc::now() + chrono::Duration::days(10))
+        .format("%Y-%m-%d")
+        .to_string();
+    assert_eq!(date_of(&forecast["expected_completion"]), expected);
+    // Without any variation in the daily burn the range collapses to the expected date.
+    assert_eq!(date_of(&forecast["earliest_completion"]), expected);
+    assert_eq!(date_of(&forecast["latest_completion"]), expected);
+}
+
+#[test]
+fn widens_the_range_for_uneven_burn() {
+    let server = Server::start_with_epic("forecast-uneven", "", "FC-1");
+    for (day, remaining) in [
+        (6, 60),
+        (5, 52),
+        (4, 52),
+        (3, 40),
+        (2, 39),
+        (1, 30),
+        (0, 30),
+    ] {
+        server.insert_snapshot(
+            "FC-11",
+            "FC-1",
+            "alice",
+            (60 - remaining) * HOUR,
+            remaining * HOUR,
+            days_ago(day),
+        );
+    }
+
+    let forecast = server.get_json("/api/v1/epics/FC-1/forecast");
+    assert_eq!(forecast["status"], "projected");
+    let earliest = date_of(&forecast["earliest_completion"]);
+    let expected = date_of(&forecast["expected_completion"]);
+    let latest = date_of(&forecast["latest_completion"]);
+    assert!(earliest < expected && expected < latest, "{}", forecast);
+}
+
+#[test]
+fn only_measures_the_trailing_window() {
+    let server =
+        Server::start_with_epic("forecast-window", "[forecast]\nwindow_days = 3\n", "FC-1");
+    // Worked down until three days ago, nothing happened since.
+    for day in 3..=10 {
+        server.insert_snapshot(
+            "FC-11",
+            "FC-1",
+            "alice",
+            (10 - day) * 4 * HOUR,
+            (40 + day * 4) * HOUR,
+            days_ago(day),
+        );
+    }
+
+    let forecast = server.get_json("/api/v1/epics/FC-1/forecast");
+    assert_eq!(forecast["window_days"], 3);
+    assert_eq!(forecast["status"], "no_progress");
+    assert!(forecast["expected_completion"].is_null());
+}
+
+#[test]
+fn reports_done_and_missing_history() {
+    let server = Server::start_with_epic("forecast-states", "", "FC-1");
+    server.insert_epic("FC-2", "Finished epic");
+    server.insert_snapshot("FC-11", "FC-1", "alice", 0, 8 * HOUR, days_ago(0));
+    server.insert_snapshot("FC-21", "FC-2", "alice", 8 * HOUR, 0, days_ago(3));
+
+    assert_eq!(
+        server.get_json("/api/v1/epics/FC-1/forecast")["status"],
+        "insufficient_history"
+    );
+    assert_eq!(
+        server.get_json("/api/v1/epics/FC-2/forecast")["status"],
+        "done"
+    );
+}
+
+#[test]
+fn shows_the_forecast_on_the_root_page_and_epic_graph() {
+    let server = Server::start_with_epic("forecast-pages", "", "FC-1");
+    insert_steady_burn(&server);
+
+    let page = reqwest::blocking::get(server.url("/"))
+        .unwrap()
+        .text()
+        .unwrap();
+    assert!(page.contains("Forecast: done around"), "{}", page);
+    assert!(page.contains("4.0h/day"));
+
+    let graph = |metrics: &str| {
+        reqwest::blocking::get(
+            server.url(&format!("/epic/FC-1/time_graph.svg?metrics={}", metrics)),
+        )
+        .unwrap()
+        .text()
+        .unwrap()
+    };
+    assert_eq!(
+        graph("time_spent,remaining_estimate")
+            .matches("stroke-dasharray")
+            .count(),
+        2
+    );
+    assert_eq!(
+        graph("original_estimate,time_spent")
+            .matches("stroke-dasharray")
+            .count(),
+        1
+    );
+    assert!(!graph("original_estimate,story_points").contains("stroke-dasharray"));
+}
@@ -0,0 +1,180 @@
+//! Simulates completion dates from per-assignee snapshot histories stored directly in the database.
+
+mod common;
+
+use common::{days_ago, Server, HOUR};
+
+/// Daily snapshots of an issue over the last ten days, logging `hours(day)` on each day, oldest
+/// first, and ending with `remaining` hours left.
+fn insert_history(
+    server: &Server,
+    key: &str,
+    assignee: &str,
+    remaining: i64,
+    hours: impl Fn(i64) -> i64,
+) {
+    let logged = (1..=10).map(&hours).sum::<i64>();
+    let mut spent = 0;
+    for day in 0..=10 {
+        spent += if day == 0 { 0 } else { hours(day) };
+        server.insert_snapshot(
+            key,
+            "MC-1",
+            assignee,
+            spent * HOUR,
+            (remaining + logged - spent) * HOUR,
+            days_ago(10 - day),
+        );
+    }
+}
+
+fn percentile_days(forecast: &serde_json::Value) -> Vec<i64> {
+    forecast["percentiles"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|percentile| percentile["days"].as_i64().unwrap())
+        .collect()
+}
+
+#[test]
+fn slowest_assignee_decides_completion() {
+    let server = Server::start_with_epic("monte-carlo-slowest", "", "MC-1");
+    insert_history(&server, "MC-11", "alice", 40, |_| 4);
+    insert_history(&server, "MC-12", "bob", 4, |_| 4);
+
+    let forecast = server.get_json("/api/v1/epics/MC-1/monte-carlo");
+    assert_eq!(forecast["status"], "projected");
+    assert_eq!(forecast["history_days"], 10);
+    assert_eq!(forecast["remaining_estimate"], 44 * HOUR);
+    assert_eq!(forecast["assignees"][0]["assignee"], "alice");
+    assert_eq!(
+        forecast["assignees"][0]["time_spent_per_day"],
+        4.0 * HOUR as f64
+    );
+    assert_eq!(forecast["assignees"][0]["active_days"], 10);
+
+    // Alice always needs ten days, however fast the team as a whole is.
+    assert_eq!(percentile_days(&forecast), vec![10, 10, 10]);
+    assert_eq!(
+        forecast["histogram"],
+        serde_json::json!([{"days": 10, "runs": 1000}])
+    );
+    let expected = (chrono::Utc::now() + chrono::Duration::days(10))
+        .format("%Y-%m-%d")
+        .to_string();
+    assert_eq!(
+        &forecast["percentiles"][1]["completion"].as_str().unwrap()[..10],
+        expected
+    );
+}
+
+#[test]
+fn uneven_throughput_spreads_completion_dates() {
+    let server = Server::start_with_epic(
+        "monte-carlo-uneven",
+        "[forecast]\nsimulations = 500\n",
+        "MC-1",
+    );
+    insert_history(&server, "MC-11", "alice", 40, |day| {
+        if day % 2 == 0 {
+            8
+        } else {
+            0
+        }
+    });
+
+    let forecast = server.get_json("/api/v1/epics/MC-1/monte-carlo");
+    assert_eq!(forecast["simulations"], 500);
+    assert_eq!(forecast["assignees"][0]["active_days"], 5);
+    let days = percentile_days(&forecast);
+    assert!(days[0] >= 5 && days[0] < days[2], "{:?}", days);
+    assert!(days[0] <= days[1] && days[1] <= days[2], "{:?}", days);
+
+    let histogram = forecast["histogram"].as_array().unwrap();
+    assert!(histogram.len() > 1);
+    let runs = histogram
+        .iter()
+        .map(|bin| bin["runs"].as_u64().unwrap())
+        .sum::<u64>();
+    assert_eq!(runs, 500);
+
+    // The seed is fixed, so the same snapshots give the same forecast.
+    let again = server.get_json("/api/v1/epics/MC-1/monte-carlo");
+    assert_eq!(again["histogram"], forecast["histogram"]);
+    assert_eq!(percentile_days(&again), days);
+}
+
+#[test]
+fn unassigned_work_uses_everyones_throughput() {
+    let server = Server::start_with_epic("monte-carlo-unassigned", "", "MC-1");
+    insert_history(&server, "MC-11", "alice", 0, |_| 2);
+    insert_history(&server, "MC-12", "bob", 0, |_| 6);
+    server.insert_snapshot("MC-13", "MC-1", "", 0, 8 * HOUR, days_ago(0));
+
+    let forecast = server.get_json("/api/v1/epics/MC-1/monte-carlo");
+    assert_eq!(forecast["status"], "projected");
+    assert_eq!(forecast["assignees"].as_array().unwrap().len(), 1);
+    assert_eq!(forecast["assignees"][0]["assignee"], "");
+    assert_eq!(
+        forecast["assignees"][0]["time_spent_per_day"],
+        4.0 * HOUR as f64
+    );
+    assert_eq!(forecast["assignees"][0]["active_days"], 20);
+    let days = percentile_days(&forecast);
+    assert!(days[0] >= 2 && days[2] <= 4, "{:?}", days);
+}
+
+#[test]
+fn reports_done_no_progress_and_missing_history() {
+    let server = Server::start_with_epic("monte-carlo-states", "", "MC-1");
+    server.insert_epic("MC-2", "Finished epic");
+    server.insert_epic("MC-3", "New epic");
+    insert_history(&server, "MC-11", "alice", 40, |_| 4);
+    server.insert_snapshot("MC-12", "MC-1", "carol", 0, 8 * HOUR, days_ago(5));
+    server.insert_snapshot("MC-21", "MC-2", "alice", 8 * HOUR, 0, days_ago(3));
+    server.insert_snapshot("MC-31", "MC-3", "alice", 0, 8 * HOUR, days_ago(0));
+
+    // Carol never logged any time, so her issue never gets done.
+    let forecast = server.get_json("/api/v1/epics/MC-1/monte-carlo");
+    assert_eq!(forecast["status"], "no_progress");
+    assert_eq!(forecast["unfinished_runs"], 1000);
+    assert!(forecast["percentiles"].as_array().unwrap().is_empty());
+
+    assert_eq!(
+        server.get_json("/api/v1/epics/MC-2/monte-carlo")["status"],
+        "done"
+    );
+    assert_eq!(
+        server.get_json("/api/v1/epics/MC-3/monte-carlo")["status"],
+        "insufficient_history"
+    );
+}
+
+#[test]
+fn renders_the_histogram_with_percentile_markers() {
+    let server = Server::start_with_epic("monte-carlo-histogram", "", "MC-1");
+    insert_history(&server, "MC-11", "alice", 40, |day| day % 3 * 3);
+
+    let response = reqwest::blocking::get(server.url("/epic/MC-1/forecast.svg")).unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "image/svg+xml");
+    let svg = response.text().unwrap();
+    assert!(svg.contains("<rect"));
+    for label in ["P50 ", "P85 ", "P95 "] {
+        assert!(
+            svg.contains(label),
+            "Histogram does not contain {:?}",
+            label
+        );
+    }
+
+    let missing = reqwest::blocking::get(server.url("/epic/MC-9/forecast.svg")).unwrap();
+    assert_eq!(missing.status(), 404);
+
+    let page = reqwest::blocking::get(server.url("/"))
+        .unwrap()
+        .text()
+        .unwrap();
+    assert!(page.contains("/epic/MC-1/forecast.svg"));
+}
@@ -0,0 +1,188 @@
+//! Serves the dashboard with `--fixtures`, collecting the demo fixtures, and checks the pages,
+//! graphs and API.
+
+mod common;
+
+use common::{demo_fixtures_dir, Server};
+
+fn start(name: &str, args: &[&str]) -> Server {
+    let fixtures_dir = demo_fixtures_dir();
+    let args = [&["--fixtures", fixtures_dir.to_str().unwrap()], args].concat();
+    let server = Server::start(
+        name,
+        "[jira]\nstory_points_field = \"Story Points\"\n",
+        &args,
+    );
+    server.wait_for_first_sync();
+    server
+}
+
+fn get(server: &Server, path: &str) -> reqwest::blocking::Response {
+    reqwest::blocking::get(server.url(path)).expect("Request failed")
+}
+
+#[test]
+fn root_page_lists_epics_and_issues() {
+    let server = start("routes-root", &["serve"]);
+    let response = get(&server, "/");
+    assert_eq!(response.status(), 200);
+    let page = response.text().unwrap();
+
+    for text in [
+        "Customer portal redesign",
+        "Invoice export",
+        "Single sign-on",
+        "DEMO-22",
+        "PDF export of invoices",
+        "Last synced",
+    ] {
+        assert!(page.contains(text), "Root page does not contain {:?}", text);
+    }
+}
+
+#[test]
+fn graphs_are_svg() {
+    let server = start("routes-graphs", &["serve"]);
+
+    for path in [
+        "/epic/DEMO-1/time_graph.svg",
+        "/issue/DEMO-11/time_graph.svg",
+        "/epic/DEMO-1/time_graph.svg?metrics=story_points,remaining_estimate",
+    ] {
+        let response = get(&server, path);
+        assert_eq!(response.status(), 200, "{}", path);
+        assert_eq!(response.headers()["content-type"], "image/svg+xml");
+        assert!(response.text().unwrap().contains("<svg"), "{}", path);
+    }
+}
+
+#[test]
+fn epic_sprints_page_shows_progress_per_sprint() {
+    let server = start("routes-sprints", &["serve"]);
+    let page = get(&server, "/epic/DEMO-1/sprints").text().unwrap();
+    assert!(page.contains("DEMO Sprint 11"));
+    assert!(page.contains("DEMO Sprint 12"));
+
+    let sprints = server.get_json("/api/v1/epics/DEMO-1/sprints");
+    assert!(sprints.to_string().contains("DEMO Sprint 12"));
+}
+
+#[test]
+fn api_serves_metrics_health_and_pages() {
+    let server = start("routes-api", &["serve"]);
+
+    let epic = server.get_json("/api/v1/epics/DEMO-2");
+    assert_eq!(epic["summary"], "Invoice export");
+
+    let metrics = server.get_json("/api/v1/epics/DEMO-2/metrics");
+    assert_eq!(metrics["time_estimate"], 48 * 3600);
+    assert_eq!(metrics["time_spent"], 53 * 3600);
+
+    let health = server.get_json("/api/v1/epics/DEMO-2/health");
+    assert!(health["status"].is_string());
+
+    let first_page = server.get_json("/api/v1/issues?per_page=4");
+    assert_eq!(first_page["total"], 10);
+    assert_eq!(first_page["items"].as_array().unwrap().len(), 4);
+    let last_page = server.get_json("/api/v1/issues?per_page=4&page=3");
+    assert_eq!(last_page["items"].as_array().unwrap().len(), 2);
+}
+
+#[test]
+fn errors_are_rendered_as_json_or_html() {
+    let server = start("routes-errors", &["serve"]);
+
+    let json = reqwest::blocking::Client::new()
+        .get(server.url("/api/v1/epics/DEMO-9"))
+        .header("Accept", "application/json")
+        .send()
+        .unwrap();
+    assert_eq!(json.status(), 404);
+    let body: serde_json::Value = json.json().unwrap();
+    assert_eq!(body["status"], 404);
+    assert!(body["error"].as_str().unwrap().contains("DEMO-9"));
+
+    let html = get(&server, "/epic/DEMO-9/sprints");
+    assert_eq!(html.status(), 404);
+    assert!(html.text().unwrap().contains("<html>"));
+}
+
+#[test]
+fn sync_endpoint_collects_again() {
+    let server = start("routes-sync", &["serve"]);
+    let client = reqwest::blocking::Client::new();
+
+    let response = client.post(server.url("/api/v1/sync")).send().unwrap();
+    assert_eq!(response.status(), 200);
+    let run: serde_json::Value = response.json().unwrap();
+    assert_eq!(run["issues_fetched"], 10);
+    assert_eq!(run["snapshots_written"], 0);
+
+    let response = client
+        .post(server.url("/api/v1/sync?epic_key=DEMO-3"))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(
+        response.json::<serde_json::Value>().unwrap()["issues_fetched"],
+        3
+    );
+}
+
+#[test]
+fn sync_button_escapes_the_error() {
+    let server = start("routes-sync-button", &["serve"]);
+
+    let response = reqwest::blocking::Client::new()
+        .post(server.url("/sync"))
+        .query(&[("epic_key", "<img src=x onerror=alert(1)>")])
+        .send()
+        .unwrap();
+    let body = response.text().unwrap();
+    assert!(body.contains("Sync failed: "), "{}", body);
+    assert!(body.contains("&lt;img src=x onerror=alert(1)&gt;"), "{}", body);
+    assert!(!body.contains("<img"));
+}
+
+#[test]
+fn sync_endpoint_is_disabled_without_collector() {
+    let fixtures_dir = demo_fixtures_dir();
+    let server = Server::start(
+        "routes-no-collector",
+        "",
+        &[
+            "--fixtures",
+            fixtures_dir.to_str().unwrap(),
+            "serve",
+            "--no-collector",
+        ],
+    );
+
+    let response = reqwest::blocking::Client::new()
+        .post(server.url("/api/v1/sync"))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 403);
+    assert_eq!(server.get_json("/api/v1/epics")["total"], 0);
+}
+
+#[test]
+fn sync_errors_are_escaped_on_the_root_page() {
+    let server = Server::start("routes-sync-error", "", &["serve", "--no-collector"]);
+    server
+        .database()
+        .execute(
+            "INSERT INTO sync_runs (started_at, finished_at, full_sync, epics_fetched, issues_fetched, snapshots_written, issues_removed, error)
+            VALUES (?1, ?1, 1, 0, 0, 0, 0, ?2)",
+            rusqlite::params![
+                chrono::Utc::now().to_rfc3339(),
+                "Unexpected response from Jira (400): <script>alert(1)</script>"
+            ],
+        )
+        .unwrap();
+
+    let page = get(&server, "/").text().unwrap();
+    assert!(page.contains("last sync failed"));
+    assert!(page.contains("&lt;script&gt;alert(1)&lt;/script&gt;"));
+    assert!(!page.contains("<script>alert"));
+}
@@ -0,0 +1,297 @@
+//! Replays recorded Jira webhook payloads against the webhook endpoint.
+
+mod common;
+
+use common::mock_jira::{Fixtures, MockJira};
+use common::{fixture, start_mock_jira, Server};
+use hmac::{Hmac, Mac};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const SECRET: &str = "s3cret";
+
+/// The issue as Jira returns it after the worklog of `worklog_created.json` was added.
+fn issue_after_worklog() -> serde_json::Value {
+    let payload: serde_json::Value =
+        serde_json::from_slice(&fixture("webhooks/issue_updated.json")).unwrap();
+    let mut issue = payload["issue"].clone();
+    issue["fields"]["aggregatetimespent"] = 5400.into();
+    issue["fields"]["aggregatetimeestimate"] = 5400.into();
+    issue
+}
+
+fn mock_jira() -> Arc<MockJira> {
+    let fixtures = Fixtures {
+        epics: vec![serde_json::json!({
+            "id": "10100",
+            "key": "BILL-1",
+            "fields": {
+                "summary": "Billing integration",
+                "issuetype": {"name": "Epic"},
+                "created": "2024-03-01T09:00:00.000+0000",
+            },
+        })],
+        ..Fixtures::default()
+    };
+    Arc::new(MockJira::new(fixtures, 50))
+}
+
+/// Serves with the collector, which webhooks need, and adds the issue to Jira after its first
+/// sync so that only the webhooks store it.
+fn start_server(name: &str, mock: &Arc<MockJira>) -> Server {
+    let jira_url = start_mock_jira(mock.clone());
+    let server = Server::start(
+        name,
+        &format!(
+            "[jira]\nbase_url = {:?}\ntoken = \"test\"\nepics_jql = \"project = BILL\"\nissues_jql = \"project = BILL\"\nepic_link_field = \"customfield_10014\"\nsprint_field = \"\"\n\n[collector]\ninterval_secs = 3600\n\n[webhook]\nsecret = {:?}\n",
+            jira_url, SECRET
+        ),
+        &["serve"],
+    );
+    server.wait_for_first_sync();
+    mock.update(|fixtures| fixtures.issues.push(issue_after_worklog()));
+    server
+}
+
+fn post_webhook(server: &Server, payload: &str) -> serde_json::Value {
+    let response = reqwest::blocking::Client::new()
+        .post(server.url(&format!("/api/v1/webhooks/jira?secret={}", SECRET)))
+        .header("Content-Type", "application/json")
+        .body(fixture(payload))
+        .send()
+        .unwrap();
+    assert_eq!(
+        response.status(),
+        200,
+        "{}: {}",
+        payload,
+        response.text().unwrap()
+    );
+    response.json().unwrap()
+}
+
+fn issue_keys(server: &Server) -> Vec<String> {
+    server.get_json("/api/v1/issues")["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|issue| issue["key"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn rejects_requests_without_valid_secret() {
+    let server = start_server("webhook-secret", &mock_jira());
+    let client = reqwest::blocking::Client::new();
+    let url = server.url("/api/v1/webhooks/jira");
+    let body = fixture("webhooks/issue_created.json");
+
+    let unsigned = client.post(&url).body(body.clone()).send().unwrap();
+    assert_eq!(unsigned.status(), 401);
+
+    let wrong_secret = client
+        .post(format!("{}?secret=wrong", url))
+        .body(body.clone())
+        .send()
+        .unwrap();
+    assert_eq!(wrong_secret.status(), 401);
+
+    let wrong_signature = client
+        .post(&url)
+        .header("X-Hub-Signature", "sha256=0000")
+        .body(body)
+        .send()
+        .unwrap();
+    assert_eq!(wrong_signature.status(), 401);
+
+    assert!(issue_keys(&server).is_empty());
+}
+
+#[test]
+fn accepts_signed_payloads() {
+    let server = start_server("webhook-signature", &mock_jira());
+    let body = fixture("webhooks/issue_created.json");
+    let mut mac = Hmac::<sha2::Sha256>::new_from_slice(SECRET.as_bytes()).unwrap();
+    mac.update(&body);
+    let signature = format!("sha256={}", hex::encode(mac.finalize().into_bytes()));
+
+    let response = reqwest::blocking::Client::new()
+        .post(server.url("/api/v1/webhooks/jira"))
+        .header("X-Hub-Signature", signature)
+        .body(body)
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(issue_keys(&server), ["BILL-101"]);
+}
+
+#[test]
+fn stores_snapshots_of_created_and_updated_issues() {
+    let server = start_server("webhook-updates", &mock_jira());
+
+    let created = post_webhook(&server, "webhooks/issue_created.json");
+    assert_eq!(created["issue_key"], "BILL-101");
+    assert_eq!(created["action"], "snapshot_written");
+
+    assert_eq!(
+        post_webhook(&server, "webhooks/issue_updated.json")["action"],
+        "snapshot_written"
+    );
+    assert_eq!(
+        post_webhook(&server, "webhooks/issue_updated.json")["action"],
+        "unchanged"
+    );
+
+    let issue = &server.get_json("/api/v1/issues")["items"][0];
+    assert_eq!(issue["epic_key"], "BILL-1");
+    assert_eq!(issue["time_estimate"], 10800);
+    assert_eq!(issue["time_spent"], 3600);
+    assert_eq!(issue["status"], "In Progress");
+    assert_eq!(issue["assignee"], "Alice Example");
+
+    let history = server.get_json("/api/v1/issues/BILL-101/history");
+    assert_eq!(history["total"], 2);
+}
+
+#[test]
+fn fetches_the_issue_of_worklog_events() {
+    let server = start_server("webhook-worklog", &mock_jira());
+    post_webhook(&server, "webhooks/issue_updated.json");
+
+    let outcome = post_webhook(&server, "webhooks/worklog_created.json");
+    assert_eq!(outcome["issue_key"], "BILL-101");
+    assert_eq!(outcome["action"], "snapshot_written");
+
+    let issue = &server.get_json("/api/v1/issues")["items"][0];
+    assert_eq!(issue["time_spent"], 5400);
+    assert_eq!(issue["remaining_estimate"], 5400);
+}
+
+#[test]
+fn records_removals_of_deleted_and_moved_issues() {
+    let server = start_server("webhook-removals", &mock_jira());
+
+    post_webhook(&server, "webhooks/issue_created.json");
+    assert_eq!(
+        post_webhook(&server, "webhooks/issue_updated_other_epic.json")["action"],
+        "removed"
+    );
+    assert!(issue_keys(&server).is_empty());
+
+    // Moving it back makes it count again, deleting it removes it for good.
+    assert_eq!(
+        post_webhook(&server, "webhooks/issue_updated.json")["action"],
+        "snapshot_written"
+    );
+    assert_eq!(issue_keys(&server), ["BILL-101"]);
+    assert_eq!(
+        post_webhook(&server, "webhooks/issue_deleted.json")["action"],
+        "removed"
+    );
+    assert!(issue_keys(&server).is_empty());
+
+    let removals = server.get_json("/api/v1/removals");
+    let reasons = removals["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|removal| removal["reason"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(reasons, ["deleted", "moved_to_other_epic"]);
+}
+
+#[test]
+fn ignores_untracked_epics_and_other_events() {
+    let server = start_server("webhook-ignored", &mock_jira());
+
+    assert_eq!(
+        post_webhook(&server, "webhooks/issue_created_untracked_epic.json")["action"],
+        "ignored"
+    );
+    assert_eq!(
+        post_webhook(&server, "webhooks/comment_created.json")["action"],
+        "ignored"
+    );
+    assert_eq!(
+        post_webhook(&server, "webhooks/issue_deleted.json")["action"],
+        "ignored"
+    );
+    assert!(issue_keys(&server).is_empty());
+}
+
+#[test]
+fn records_removals_of_issues_no_longer_matching_the_jql() {
+    let mock = mock_jira();
+    let server = start_server("webhook-jql", &mock);
+    post_webhook(&server, "webhooks/issue_created.json");
+
+    mock.update(|fixtures| fixtures.issues.clear());
+    assert_eq!(
+        post_webhook(&server, "webhooks/issue_updated.json")["action"],
+        "removed"
+    );
+    assert!(issue_keys(&server).is_empty());
+    assert_eq!(
+        server.get_json("/api/v1/removals")["items"][0]["reason"],
+        "no_longer_matches_jql"
+    );
+
+    // Issues that never matched are not stored in the first place.
+    assert_eq!(
+        post_webhook(&server, "webhooks/issue_created.json")["action"],
+        "ignored"
+    );
+    assert!(issue_keys(&server).is_empty());
+}
+
+#[test]
+fn defers_webhooks_while_syncing() {
+    let mock = mock_jira();
+    let server = start_server("webhook-deferred", &mock);
+    mock.delay(Duration::from_secs(2));
+    let sync = std::thread::spawn({
+        let url = server.url("/api/v1/sync");
+        move || reqwest::blocking::Client::new().post(url).send().unwrap()
+    });
+    while server.get_json("/api/v1/sync-runs")["items"][0]["finished_at"].is_string() {
+        std::thread::sleep(Duration::from_millis(20));
+    }
+
+    let started = Instant::now();
+    let response = reqwest::blocking::Client::new()
+        .post(server.url(&format!("/api/v1/webhooks/jira?secret={}", SECRET)))
+        .body(fixture("webhooks/issue_created.json"))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 503);
+    assert!(started.elapsed() < Duration::from_secs(1));
+
+    mock.delay(Duration::ZERO);
+    assert_eq!(sync.join().unwrap().status(), 200);
+    assert_eq!(
+        post_webhook(&server, "webhooks/issue_created.json")["action"],
+        "snapshot_written"
+    );
+}
+
+#[test]
+fn rejects_webhooks_without_collector() {
+    let jira_url = start_mock_jira(mock_jira());
+    let server = Server::start(
+        "webhook-no-collector",
+        &format!(
+            "[jira]\nbase_url = {:?}\ntoken = \"test\"\n\n[webhook]\nsecret = {:?}\n",
+            jira_url, SECRET
+        ),
+        &["serve", "--no-collector"],
+    );
+    server.insert_epic("BILL-1", "Billing integration");
+
+    let response = reqwest::blocking::Client::new()
+        .post(server.url(&format!("/api/v1/webhooks/jira?secret={}", SECRET)))
+        .body(fixture("webhooks/issue_created.json"))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), 403);
+    assert!(issue_keys(&server).is_empty());
+}